@@ -1,10 +1,13 @@
-use std::{env::args, fs::read_dir, io::ErrorKind, path::PathBuf};
-
-// Required for FS size on Unix
-use std::os::unix::fs::MetadataExt;
+use std::{
+    env::args,
+    io::Error,
+    path::{Path, PathBuf},
+};
 
 use clap::Parser;
 
+use crate::utils::{DiskUsage, Usage};
+
 #[derive(Parser, Debug, Clone)]
 #[command(
     version,
@@ -100,9 +103,10 @@ pub fn main() {
         return;
     }
 
+    let mut disk_usage = DiskUsage::new(cli.dereference);
     let mut total = 0;
     for file in cli.file.iter() {
-        total += du_print(file, &cli).unwrap_or(0);
+        total += du_print(file, &cli, &mut disk_usage).unwrap_or(0);
     }
 
     if cli.total {
@@ -110,10 +114,20 @@ pub fn main() {
     }
 }
 
-fn path_string(p: &PathBuf) -> String {
+fn path_string(p: &Path) -> String {
     p.to_string_lossy().to_string()
 }
 
+/// The error message without the "(os error N)" at the end
+fn error_text(err: &Error) -> String {
+    let message = err.to_string();
+    message
+        .split(" (os error")
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
 /// Converts bytes to KiB
 fn format_bytes(bytes: u64 /*, format: */) -> u64 {
     // TODO: universal support for byte conversion to KiB MiB GiB, etc.
@@ -124,75 +138,48 @@ fn print_bytes(bytes: u64, message: &str) {
     println!("{}\t{}", format_bytes(bytes), message);
 }
 
-fn du_print(file: &PathBuf, cli: &Cli) -> Option<u64> {
-    let size = du(file, cli);
-    if let Some(size) = size {
-        print_bytes(size, &path_string(file));
-    }
-    return size;
-}
-
-fn du(file: &PathBuf, cli: &Cli) -> Option<u64> {
-    let file_symlink = file.is_symlink();
-    if !file.exists() && (cli.dereference || !file_symlink) {
-        eprintln!(
-            "du: cannot access '{}': No such file or directory",
-            path_string(file)
-        );
-        return None;
-    }
-
-    let file_metadata = match if cli.dereference {
-        file.metadata()
-    } else {
-        file.symlink_metadata()
-    } {
-        Ok(metadata) => metadata,
-        Err(err) => {
-            // NOTE: this is not GNU coreutils compliant
-            eprintln!("du: cannot read metadata '{}': {}", path_string(file), err);
-            return None;
-        }
-    };
-
-    let mut file_size = if cli.apparent_size {
-        file_metadata.len()
+fn bytes(usage: Usage, cli: &Cli) -> u64 {
+    if cli.apparent_size {
+        usage.apparent
     } else {
         // linux st_blocks, which returns the st_blocks result in 512-byte units:
         // https://doc.rust-lang.org/std/os/linux/fs/trait.MetadataExt.html#tymethod.st_blocks
-        file_metadata.blocks() * 512
-    };
+        usage.blocks * 512
+    }
+}
 
-    if file.is_dir() && (cli.dereference || !file_symlink) {
-        let dir_content = match read_dir(&file) {
-            Ok(content) => content,
-            Err(err) => {
-                let msg = match err.kind() {
-                    ErrorKind::PermissionDenied => "Permission denied",
-                    _ => &err.to_string(),
-                };
-                eprintln!("du: cannot read directory '{}': {}", path_string(file), msg);
-                return Some(file_size);
-            }
-        };
-        for entry in dir_content {
-            if let Err(err) = entry {
-                // NOTE: this is not GNU coreutils compliant
-                eprintln!(
-                    "du: cannot access entry in directory '{}': {}",
-                    path_string(file),
-                    err
-                );
-                continue;
+/// Prints the size of the file and, unless summarizing, of the directories below it. Hard links
+/// are only counted once across all files
+fn du_print(file: &Path, cli: &Cli, disk_usage: &mut DiskUsage) -> Option<u64> {
+    let usage = disk_usage.walk(
+        file,
+        &mut |entry, metadata, usage| {
+            if !cli.summarize && (cli.all || metadata.is_dir()) {
+                print_bytes(bytes(usage, cli), &path_string(entry));
             }
-            let entry = &entry.unwrap().path(); // NOTE: entry.metadata() is lost here
-            file_size += if !cli.summarize && (cli.all || entry.is_dir()) {
-                du_print(entry, cli).unwrap_or(0)
-            } else {
-                du(entry, cli).unwrap_or(0)
-            };
+        },
+        &mut |action, entry, err| {
+            eprintln!(
+                "du: {} '{}': {}",
+                action,
+                path_string(entry),
+                error_text(err)
+            );
+        },
+    );
+    match usage {
+        Ok(usage) => {
+            let size = bytes(usage?, cli);
+            print_bytes(size, &path_string(file));
+            Some(size)
+        }
+        Err(err) => {
+            eprintln!(
+                "du: cannot access '{}': {}",
+                path_string(file),
+                error_text(&err)
+            );
+            None
         }
     }
-
-    Some(file_size)
 }
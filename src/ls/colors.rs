@@ -0,0 +1,356 @@
+//! Colouring of entry names. Configured through the LS_COLORS environment variable, which uses
+//! the same format as GNU ls and dircolors
+
+use std::{
+    cell::Cell,
+    env::var,
+    fs::Metadata,
+    os::unix::fs::{FileTypeExt, MetadataExt},
+};
+
+use libc::{S_ISGID, S_ISUID, S_ISVTX, S_IWOTH, S_IXGRP, S_IXOTH, S_IXUSR};
+
+/// All two letter keys LS_COLORS understands, in the same order as `Indicator`
+const INDICATOR_KEYS: [&str; 24] = [
+    "lc", "rc", "ec", "rs", "no", "fi", "di", "ln", "pi", "so", "bd", "cd", "mi", "or", "ex", "do",
+    "su", "sg", "st", "ow", "tw", "ca", "mh", "cl",
+];
+
+/// The keys which decide how an entry is coloured, numbered by their place in `INDICATOR_KEYS`.
+/// "no", "ca" and "cl" are accepted but never used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indicator {
+    Left = 0,
+    Right = 1,
+    End = 2,
+    Reset = 3,
+    File = 5,
+    Directory = 6,
+    Link = 7,
+    Pipe = 8,
+    Socket = 9,
+    BlockDevice = 10,
+    CharDevice = 11,
+    Missing = 12,
+    Orphan = 13,
+    Executable = 14,
+    Door = 15,
+    Setuid = 16,
+    Setgid = 17,
+    Sticky = 18,
+    OtherWritable = 19,
+    StickyOtherWritable = 20,
+    MultiHardlink = 22,
+}
+
+#[derive(Debug, Clone)]
+pub struct LsColors {
+    indicators: [Option<String>; 24],
+    /// Suffixes like ".tar.gz" from the "*.tar.gz=..." keys, in the order they were defined
+    extensions: Vec<Extension>,
+    /// Set by "ln=target", colours links like the file they point to
+    link_as_target: bool,
+    /// GNU resets the terminal colours once before the first coloured name
+    used: Cell<bool>,
+}
+
+#[derive(Debug, Clone)]
+struct Extension {
+    suffix: String,
+    /// The suffix in lower case, worked out once so matching a name doesn't allocate
+    lowercase: String,
+    /// Whether another suffix only differs in case, then this one has to match exactly
+    ambiguous: bool,
+    sequence: String,
+}
+
+impl Default for LsColors {
+    /// The colours GNU uses when LS_COLORS isn't set
+    fn default() -> Self {
+        let mut indicators: [Option<String>; 24] = Default::default();
+        for (indicator, sequence) in [
+            (Indicator::Left, "\x1b["),
+            (Indicator::Right, "m"),
+            (Indicator::Reset, "0"),
+            (Indicator::Directory, "01;34"),
+            (Indicator::Link, "01;36"),
+            (Indicator::Pipe, "33"),
+            (Indicator::Socket, "01;35"),
+            (Indicator::BlockDevice, "01;33"),
+            (Indicator::CharDevice, "01;33"),
+            (Indicator::Executable, "01;32"),
+            (Indicator::Door, "01;35"),
+            (Indicator::Setuid, "37;41"),
+            (Indicator::Setgid, "30;43"),
+            (Indicator::Sticky, "37;44"),
+            (Indicator::OtherWritable, "34;42"),
+            (Indicator::StickyOtherWritable, "30;42"),
+        ] {
+            indicators[indicator as usize] = Some(sequence.to_string());
+        }
+        LsColors {
+            indicators,
+            extensions: vec![],
+            link_as_target: false,
+            used: Cell::new(false),
+        }
+    }
+}
+
+impl LsColors {
+    /// Reads LS_COLORS on top of the default colours. Just like GNU, colouring is turned off with
+    /// a warning if the variable can't be parsed
+    pub fn from_env() -> Option<LsColors> {
+        let mut colors = LsColors::default();
+        if let Ok(spec) = var("LS_COLORS") {
+            if colors.parse(spec.as_bytes()).is_none() {
+                eprintln!("ls: unparsable value for LS_COLORS environment variable");
+                return None;
+            }
+        }
+        Some(colors)
+    }
+
+    fn parse(&mut self, mut spec: &[u8]) -> Option<()> {
+        while let Some(first) = spec.first() {
+            if *first == b':' {
+                spec = &spec[1..];
+                continue;
+            }
+
+            if *first == b'*' {
+                let (suffix, rest) = unescape(&spec[1..], true)?;
+                let (sequence, rest) = unescape(rest.strip_prefix(b"=")?, false)?;
+                let suffix = String::from_utf8_lossy(&suffix).to_string();
+                self.extensions.push(Extension {
+                    lowercase: suffix.to_ascii_lowercase(),
+                    suffix,
+                    ambiguous: false,
+                    sequence: String::from_utf8_lossy(&sequence).to_string(),
+                });
+                spec = rest;
+            } else {
+                let key = std::str::from_utf8(spec.get(..2)?).ok()?;
+                let (sequence, rest) = unescape(spec[2..].strip_prefix(b"=")?, false)?;
+                let index = INDICATOR_KEYS.iter().position(|k| *k == key)?;
+                if index == Indicator::Link as usize && sequence == b"target" {
+                    self.link_as_target = true;
+                } else {
+                    self.indicators[index] = Some(String::from_utf8_lossy(&sequence).to_string());
+                }
+                spec = rest;
+            }
+        }
+
+        // Like GNU, suffixes are matched case insensitively unless there is another one which
+        // only differs in case
+        for i in 0..self.extensions.len() {
+            let (suffix, lowercase) = (&self.extensions[i].suffix, &self.extensions[i].lowercase);
+            self.extensions[i].ambiguous = self
+                .extensions
+                .iter()
+                .any(|other| other.suffix != *suffix && other.lowercase == *lowercase);
+        }
+        Some(())
+    }
+
+    fn get(&self, indicator: Indicator) -> Option<&str> {
+        self.indicators[indicator as usize].as_deref()
+    }
+
+    /// GNU treats an empty sequence or a plain reset the same as no colour at all
+    fn is_colored(&self, indicator: Indicator) -> bool {
+        !matches!(self.get(indicator), None | Some("" | "0" | "00"))
+    }
+
+    fn extension(&self, name: &str) -> Option<&str> {
+        // Later definitions win
+        let name = name.as_bytes();
+        self.extensions
+            .iter()
+            .rev()
+            .find(|extension| {
+                let Some(start) = name.len().checked_sub(extension.suffix.len()) else {
+                    return false;
+                };
+                match extension.ambiguous {
+                    true => name[start..] == *extension.suffix.as_bytes(),
+                    false => name[start..].eq_ignore_ascii_case(extension.lowercase.as_bytes()),
+                }
+            })
+            .map(|extension| extension.sequence.as_str())
+    }
+
+    /// Picks the colour for an entry the same way as GNU. `metadata` is the entry itself and
+    /// `target` is what it points to if it's a symlink, None if the link is broken
+    pub fn sequence(
+        &self,
+        name: &str,
+        metadata: Option<&Metadata>,
+        target: Option<&Metadata>,
+    ) -> Option<&str> {
+        let Some(mut metadata) = metadata else {
+            return self.get(Indicator::Missing);
+        };
+        if metadata.file_type().is_symlink() && self.link_as_target {
+            match target {
+                Some(target) => metadata = target,
+                None => return self.get(Indicator::Orphan),
+            }
+        }
+
+        let mode = metadata.mode();
+        let file_type = metadata.file_type();
+        let indicator = if file_type.is_file() {
+            if mode & S_ISUID != 0 && self.is_colored(Indicator::Setuid) {
+                Indicator::Setuid
+            } else if mode & S_ISGID != 0 && self.is_colored(Indicator::Setgid) {
+                Indicator::Setgid
+            } else if mode & (S_IXUSR | S_IXGRP | S_IXOTH) != 0
+                && self.is_colored(Indicator::Executable)
+            {
+                Indicator::Executable
+            } else if metadata.nlink() > 1 && self.is_colored(Indicator::MultiHardlink) {
+                Indicator::MultiHardlink
+            } else {
+                Indicator::File
+            }
+        } else if file_type.is_dir() {
+            let sticky = mode & S_ISVTX != 0;
+            let other_writable = mode & S_IWOTH != 0;
+            if sticky && other_writable && self.is_colored(Indicator::StickyOtherWritable) {
+                Indicator::StickyOtherWritable
+            } else if other_writable && self.is_colored(Indicator::OtherWritable) {
+                Indicator::OtherWritable
+            } else if sticky && self.is_colored(Indicator::Sticky) {
+                Indicator::Sticky
+            } else {
+                Indicator::Directory
+            }
+        } else if file_type.is_symlink() {
+            if target.is_none() && self.is_colored(Indicator::Orphan) {
+                Indicator::Orphan
+            } else {
+                Indicator::Link
+            }
+        } else if file_type.is_fifo() {
+            Indicator::Pipe
+        } else if file_type.is_socket() {
+            Indicator::Socket
+        } else if file_type.is_block_device() {
+            Indicator::BlockDevice
+        } else if file_type.is_char_device() {
+            Indicator::CharDevice
+        } else {
+            Indicator::Orphan
+        };
+
+        // Extensions only apply to regular files
+        if indicator == Indicator::File {
+            if let Some(sequence) = self.extension(name) {
+                return Some(sequence);
+            }
+        }
+        self.get(indicator)
+    }
+
+    /// Picks the colour for the target of a symlink, shown after "->" in long listings
+    pub fn target_sequence(&self, name: &str, target: Option<&Metadata>) -> Option<&str> {
+        match target {
+            Some(target) => self.sequence(name, Some(target), Some(target)),
+            None if self.is_colored(Indicator::Missing) => self.get(Indicator::Missing),
+            None => self.get(Indicator::Orphan),
+        }
+    }
+
+    /// Wraps the text in the escape codes for the sequence. The text is bytes since file names
+    /// don't have to be UTF-8
+    pub fn paint(&self, text: &[u8], sequence: Option<&str>) -> Vec<u8> {
+        let Some(sequence) = sequence.filter(|sequence| !sequence.is_empty()) else {
+            return text.to_vec();
+        };
+        let left = self.get(Indicator::Left).unwrap_or_default();
+        let right = self.get(Indicator::Right).unwrap_or_default();
+        let end = match self.get(Indicator::End) {
+            Some(end) => end.to_string(),
+            None => format!(
+                "{}{}{}",
+                left,
+                self.get(Indicator::Reset).unwrap_or_default(),
+                right
+            ),
+        };
+        let reset = if self.used.replace(true) {
+            ""
+        } else {
+            end.as_str()
+        };
+        [
+            format!("{}{}{}{}", reset, left, sequence, right).as_bytes(),
+            text,
+            end.as_bytes(),
+        ]
+        .concat()
+    }
+}
+
+/// Decodes one key or value of LS_COLORS, stopping at ':' or, for keys, '='. Understands the
+/// same escapes as GNU: backslash escapes like \e and \033, and caret notation like ^[
+fn unescape(input: &[u8], is_key: bool) -> Option<(Vec<u8>, &[u8])> {
+    let mut result = vec![];
+    let mut i = 0;
+    while let Some(&c) = input.get(i) {
+        match c {
+            b':' => break,
+            b'=' if is_key => break,
+            b'\\' => {
+                i += 1;
+                let escaped = *input.get(i)?;
+                match escaped {
+                    b'0'..=b'7' => {
+                        let mut num: u8 = 0;
+                        while let Some(digit @ b'0'..=b'7') = input.get(i) {
+                            num = num.wrapping_mul(8).wrapping_add(digit - b'0');
+                            i += 1;
+                        }
+                        result.push(num);
+                        continue;
+                    }
+                    b'x' => {
+                        i += 1;
+                        let mut num: u8 = 0;
+                        while let Some(digit) = input.get(i).and_then(|c| (*c as char).to_digit(16))
+                        {
+                            num = num.wrapping_mul(16).wrapping_add(digit as u8);
+                            i += 1;
+                        }
+                        result.push(num);
+                        continue;
+                    }
+                    b'a' => result.push(0x07),
+                    b'b' => result.push(0x08),
+                    b'e' => result.push(0x1b),
+                    b'f' => result.push(0x0c),
+                    b'n' => result.push(b'\n'),
+                    b'r' => result.push(b'\r'),
+                    b't' => result.push(b'\t'),
+                    b'v' => result.push(0x0b),
+                    b'?' => result.push(0x7f),
+                    b'_' => result.push(b' '),
+                    other => result.push(other),
+                }
+            }
+            b'^' => {
+                i += 1;
+                match *input.get(i)? {
+                    c @ b'@'..=b'~' => result.push(c & 0x1f),
+                    b'?' => result.push(0x7f),
+                    _ => return None,
+                }
+            }
+            other => result.push(other),
+        }
+        i += 1;
+    }
+    Some((result, &input[i..]))
+}
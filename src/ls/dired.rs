@@ -0,0 +1,98 @@
+//! Output for Emacs' dired mode. Every line is indented by two spaces and a trailer lists where
+//! the file names are, so dired doesn't have to parse them out of the listing
+
+use std::{
+    cell::{Cell, RefCell},
+    io::{stdout, Write},
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct Dired {
+    /// How many bytes have been printed so far, escape codes not included
+    position: Cell<usize>,
+    /// Start and end of every file name
+    files: RefCell<Vec<(usize, usize)>>,
+    /// Start and end of every directory name in the "dir:" headers
+    subdirectories: RefCell<Vec<(usize, usize)>>,
+}
+
+impl Dired {
+    /// Prints the text and moves the position past it. Names are printed as they are, even if
+    /// they aren't UTF-8
+    pub fn print(&self, text: impl AsRef<[u8]>) {
+        let text = text.as_ref();
+        stdout().write_all(text).expect("failed printing to stdout");
+        self.position.set(self.position.get() + visible_len(text));
+    }
+
+    /// Prints the text of an entry. The file name goes from byte `name.0` to `name.1` of the text,
+    /// escape codes not counted
+    pub fn print_entry(&self, text: &[u8], name: (usize, usize)) {
+        let start = self.position.get();
+        self.files
+            .borrow_mut()
+            .push((start + name.0, start + name.1));
+        self.print(text);
+    }
+
+    /// Prints the text and records it as the name of a directory in a header
+    pub fn print_subdirectory(&self, name: &[u8]) {
+        let start = self.position.get();
+        self.print(name);
+        self.subdirectories
+            .borrow_mut()
+            .push((start, self.position.get()));
+    }
+
+    /// Prints the positions at the end of the listing. `quoting_style` is the style the names were
+    /// quoted with, which dired needs to read them back
+    pub fn print_trailer(&self, quoting_style: &str) {
+        print_positions("//DIRED//", &self.files.borrow());
+        print_positions("//SUBDIRED//", &self.subdirectories.borrow());
+        println!("//DIRED-OPTIONS// --quoting-style={}", quoting_style);
+    }
+}
+
+fn print_positions(label: &str, positions: &[(usize, usize)]) {
+    if positions.is_empty() {
+        return;
+    }
+    print!("{}", label);
+    for (start, end) in positions {
+        print!(" {} {}", start, end);
+    }
+    println!();
+}
+
+/// Length in bytes without the colour and hyperlink escape codes, which GNU doesn't count either
+fn visible_len(bytes: &[u8]) -> usize {
+    let mut len = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != 0x1b {
+            len += 1;
+            i += 1;
+            continue;
+        }
+        match bytes.get(i + 1) {
+            // CSI sequences like colours end with a byte from '@' to '~'
+            Some(b'[') => {
+                i += 2;
+                while i < bytes.len() && !(0x40..=0x7e).contains(&bytes[i]) {
+                    i += 1;
+                }
+                i += 1;
+            }
+            // OSC sequences like hyperlinks end with BEL or ESC \
+            Some(b']') => {
+                i += 2;
+                while i < bytes.len() && bytes[i] != 0x07 && bytes[i] != 0x1b {
+                    i += 1;
+                }
+                i += if bytes.get(i) == Some(&0x1b) { 2 } else { 1 };
+            }
+            _ => i += 1,
+        }
+    }
+    len
+}
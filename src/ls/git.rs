@@ -0,0 +1,1286 @@
+//! Git status of entries for --git and the ignore rules for --gitignore. The repository is read
+//! directly: the index is compared to the tree of HEAD for the staged changes and to the worktree
+//! for the unstaged ones
+
+use std::{
+    cell::{OnceCell, RefCell},
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env::var,
+    fs::{self, canonicalize, File},
+    io::{BufReader, Read, Seek, SeekFrom},
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use flate2::read::ZlibDecoder;
+use regex::Regex;
+use sha1_smol::Sha1;
+use walkdir::WalkDir;
+
+use super::colors::LsColors;
+
+/// What happened to a file, either between HEAD and the index or between the index and the
+/// worktree. The order is the priority when the changes of a whole directory are summed up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Change {
+    #[default]
+    Unmodified,
+    Ignored,
+    New,
+    Renamed,
+    Typechange,
+    Deleted,
+    Modified,
+    Conflicted,
+}
+
+impl Change {
+    fn letter(self) -> char {
+        match self {
+            Change::Unmodified => '-',
+            Change::Ignored => 'I',
+            Change::New => 'N',
+            Change::Renamed => 'R',
+            Change::Typechange => 'T',
+            Change::Deleted => 'D',
+            Change::Modified => 'M',
+            Change::Conflicted => 'U',
+        }
+    }
+
+    fn sequence(self) -> Option<&'static str> {
+        match self {
+            Change::Unmodified => None,
+            Change::Ignored => Some("37"),
+            Change::New => Some("32"),
+            Change::Renamed => Some("33"),
+            Change::Typechange => Some("35"),
+            Change::Deleted => Some("31"),
+            Change::Modified => Some("34"),
+            Change::Conflicted => Some("01;31"),
+        }
+    }
+}
+
+/// The status column of an entry: the staged change followed by the unstaged one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Status {
+    pub staged: Change,
+    pub unstaged: Change,
+}
+
+impl Status {
+    fn new(staged: Change, unstaged: Change) -> Status {
+        Status { staged, unstaged }
+    }
+
+    /// The two letters of the status, coloured if colours are on
+    pub fn paint(&self, colors: Option<&LsColors>) -> String {
+        [self.staged, self.unstaged]
+            .iter()
+            .map(|change| {
+                let letter = change.letter().to_string();
+                match colors {
+                    Some(colors) => {
+                        let painted = colors.paint(letter.as_bytes(), change.sequence());
+                        String::from_utf8_lossy(&painted).into_owned()
+                    }
+                    None => letter,
+                }
+            })
+            .collect()
+    }
+
+    fn max(self, other: Status) -> Status {
+        Status::new(
+            self.staged.max(other.staged),
+            self.unstaged.max(other.unstaged),
+        )
+    }
+}
+
+/// The repositories the listed entries are in, every repository is only read once
+#[derive(Debug, Clone, Default)]
+pub struct Repositories {
+    /// The repository of every directory entries were looked up in, None if it isn't in one
+    by_directory: RefCell<HashMap<PathBuf, Option<Rc<Repository>>>>,
+}
+
+impl Repositories {
+    /// The status of the entry, None if it isn't inside of a repository
+    pub fn status(&self, path: &Path) -> Option<Status> {
+        let (repository, relative, is_dir) = self.locate(path)?;
+        Some(repository.status(&relative, is_dir))
+    }
+
+    /// Whether git ignores the entry. Entries outside of a repository and tracked ones never are
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        self.locate(path)
+            .is_some_and(|(repository, relative, is_dir)| repository.is_ignored(&relative, is_dir))
+    }
+
+    /// The repository the entry is in, its path relative to the worktree and whether it is a
+    /// directory
+    fn locate(&self, path: &Path) -> Option<(Rc<Repository>, String, bool)> {
+        let metadata = path.symlink_metadata().ok();
+        let is_dir = metadata.as_ref().is_some_and(|m| m.is_dir());
+        // Links are files as far as git is concerned, so only their parent is resolved
+        let path = if is_dir {
+            canonicalize(path).ok()?
+        } else {
+            let parent = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            canonicalize(parent).ok()?.join(path.file_name()?)
+        };
+        let directory = if is_dir { &path } else { path.parent()? };
+
+        let repository = self
+            .by_directory
+            .borrow_mut()
+            .entry(directory.to_path_buf())
+            .or_insert_with(|| Repository::discover(directory).map(Rc::new))
+            .clone()?;
+        let relative = path.strip_prefix(&repository.workdir).ok()?;
+        Some((repository, relative.to_string_lossy().to_string(), is_dir))
+    }
+}
+
+#[derive(Debug)]
+struct Repository {
+    workdir: PathBuf,
+    /// Every path in the index
+    tracked: BTreeSet<String>,
+    /// Every entry of the index, compared to HEAD and the worktree once a status is needed
+    index: Vec<IndexEntry>,
+    git_dir: PathBuf,
+    /// The directory with the objects and refs, which linked worktrees share
+    common_dir: PathBuf,
+    /// The tracked paths which differ from HEAD or the worktree. --gitignore alone never needs them
+    changes: OnceCell<BTreeMap<String, Status>>,
+    ignore: Ignore,
+}
+
+impl Repository {
+    /// Finds the repository the directory is in by looking for a ".git" in it and its parents
+    fn discover(directory: &Path) -> Option<Repository> {
+        for workdir in directory.ancestors() {
+            let dot_git = workdir.join(".git");
+            let git_dir = if dot_git.is_dir() {
+                dot_git
+            } else if dot_git.is_file() {
+                // Worktrees and submodules point to the real git directory instead
+                let content = fs::read_to_string(&dot_git).ok()?;
+                workdir.join(content.strip_prefix("gitdir:")?.trim())
+            } else {
+                continue;
+            };
+            return Some(Repository::open(workdir, &git_dir));
+        }
+        None
+    }
+
+    fn open(workdir: &Path, git_dir: &Path) -> Repository {
+        // Linked worktrees share the objects and refs of the main repository
+        let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+            Ok(common_dir) => git_dir.join(common_dir.trim()),
+            Err(_) => git_dir.to_path_buf(),
+        };
+        let index = read_index(&git_dir.join("index")).unwrap_or_default();
+        let tracked = index.iter().map(|entry| entry.path.clone()).collect();
+        Repository {
+            workdir: workdir.to_path_buf(),
+            tracked,
+            index,
+            git_dir: git_dir.to_path_buf(),
+            common_dir,
+            changes: OnceCell::new(),
+            ignore: Ignore::new(workdir, git_dir),
+        }
+    }
+
+    /// The changes of the tracked paths, read from HEAD and the worktree the first time
+    fn changes(&self) -> &BTreeMap<String, Status> {
+        self.changes.get_or_init(|| self.read_changes())
+    }
+
+    fn read_changes(&self) -> BTreeMap<String, Status> {
+        let objects = Objects::new(&self.common_dir.join("objects"));
+        let mut head = BTreeMap::new();
+        if let Some(tree) = head_commit(&self.git_dir, &self.common_dir)
+            .and_then(|commit| objects.read(&commit))
+            .and_then(|(_, commit)| from_hex(commit.strip_prefix(b"tree ")?.get(..40)?))
+        {
+            read_tree(&objects, &tree, "", &mut head);
+        }
+
+        let mut changes = BTreeMap::new();
+        for entry in &self.index {
+            let status = if entry.stage != 0 {
+                Status::new(Change::Conflicted, Change::Conflicted)
+            } else {
+                let staged = match head.get(&entry.path) {
+                    None => Change::New,
+                    Some((mode, _)) if mode >> 12 != entry.mode >> 12 => Change::Typechange,
+                    Some((mode, sha)) if *mode != entry.mode || *sha != entry.sha => {
+                        Change::Modified
+                    }
+                    Some(_) => Change::Unmodified,
+                };
+                Status::new(staged, worktree_change(&self.workdir, entry))
+            };
+            if status != Status::default() {
+                // Conflicts have an entry for each side of the merge
+                changes
+                    .entry(entry.path.clone())
+                    .and_modify(|previous: &mut Status| *previous = previous.max(status))
+                    .or_insert(status);
+            }
+        }
+
+        // Files which were removed from the index, or renamed if a new file has the same content
+        for (path, (_, sha)) in &head {
+            if self.tracked.contains(path) {
+                continue;
+            }
+            let renamed = self.index.iter().find(|entry| {
+                entry.sha == *sha
+                    && changes
+                        .get(&entry.path)
+                        .is_some_and(|status| status.staged == Change::New)
+            });
+            match renamed {
+                Some(entry) => {
+                    if let Some(status) = changes.get_mut(&entry.path) {
+                        status.staged = Change::Renamed;
+                    }
+                }
+                None => {
+                    let unstaged = match self.workdir.join(path).symlink_metadata() {
+                        Ok(_) => Change::New,
+                        Err(_) => Change::Unmodified,
+                    };
+                    changes.insert(path.clone(), Status::new(Change::Deleted, unstaged));
+                }
+            }
+        }
+        changes
+    }
+
+    fn status(&self, path: &str, is_dir: bool) -> Status {
+        if path == ".git" || path.starts_with(".git/") {
+            return Status::default();
+        }
+        if !is_dir {
+            return match self.changes().get(path) {
+                Some(status) => *status,
+                None if self.tracked.contains(path) => Status::default(),
+                None if self.ignore.is_ignored(path, false) => {
+                    Status::new(Change::Unmodified, Change::Ignored)
+                }
+                None => Status::new(Change::Unmodified, Change::New),
+            };
+        }
+
+        // A directory sums up the changes of everything in it
+        let prefix = if path.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", path)
+        };
+        let mut status = self
+            .changes()
+            .range(prefix.clone()..)
+            .take_while(|(changed, _)| changed.starts_with(&prefix))
+            .fold(Status::default(), |status, (_, change)| status.max(*change));
+        let has_tracked = self
+            .tracked
+            .range(prefix.clone()..)
+            .next()
+            .is_some_and(|tracked| tracked.starts_with(&prefix));
+        if !has_tracked && self.ignore.is_ignored(path, true) {
+            return Status::new(status.staged, Change::Ignored);
+        }
+        if status.unstaged < Change::New && self.has_untracked(path) {
+            status.unstaged = Change::New;
+        }
+        status
+    }
+
+    /// Whether there is a file in the directory which is neither tracked nor ignored
+    fn has_untracked(&self, path: &str) -> bool {
+        let relative = |entry: &walkdir::DirEntry| {
+            entry
+                .path()
+                .strip_prefix(&self.workdir)
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default()
+        };
+        WalkDir::new(self.workdir.join(path))
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|entry| {
+                let path = relative(entry);
+                let is_dir = entry.file_type().is_dir();
+                entry.file_name() != ".git"
+                    && !(is_dir && self.ignore.is_ignored(&path, true) && !self.tracks(&path))
+            })
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| !entry.file_type().is_dir())
+            .any(|entry| {
+                let path = relative(&entry);
+                !self.tracked.contains(&path) && !self.ignore.is_ignored(&path, false)
+            })
+    }
+
+    /// Whether the ignore rules hide the path. Like git they only apply to untracked files, and
+    /// to directories without any tracked files
+    fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        if path.is_empty() {
+            return false;
+        }
+        let tracked = if is_dir {
+            self.tracks(path)
+        } else {
+            self.tracked.contains(path)
+        };
+        !tracked && self.ignore.is_ignored(path, is_dir)
+    }
+
+    /// Whether anything in the directory is tracked
+    fn tracks(&self, path: &str) -> bool {
+        let prefix = format!("{}/", path);
+        self.tracked
+            .range(prefix.clone()..)
+            .next()
+            .is_some_and(|tracked| tracked.starts_with(&prefix))
+    }
+}
+
+/// Compares the file in the worktree to its entry in the index. Like git the content is only
+/// hashed if the size or modification time changed
+fn worktree_change(workdir: &Path, entry: &IndexEntry) -> Change {
+    let path = workdir.join(&entry.path);
+    let Ok(metadata) = path.symlink_metadata() else {
+        return Change::Deleted;
+    };
+    let file_type = metadata.file_type();
+    match entry.mode >> 12 {
+        // Submodules have their own status
+        0o16 => return Change::Unmodified,
+        0o12 if file_type.is_file() => return Change::Typechange,
+        0o10 if file_type.is_symlink() => return Change::Typechange,
+        _ if file_type.is_dir() => return Change::Deleted,
+        _ => {}
+    }
+    if file_type.is_file() && (metadata.mode() & 0o100 != 0) != (entry.mode & 0o100 != 0) {
+        return Change::Modified;
+    }
+    if metadata.size() as u32 == entry.size
+        && metadata.mtime() as u32 == entry.mtime.0
+        && metadata.mtime_nsec() as u32 == entry.mtime.1
+    {
+        return Change::Unmodified;
+    }
+
+    let content = if file_type.is_symlink() {
+        fs::read_link(&path).map(|target| target.as_os_str().as_bytes().to_vec())
+    } else {
+        fs::read(&path)
+    };
+    match content {
+        Ok(content) if blob_sha(&content) == entry.sha => Change::Unmodified,
+        _ => Change::Modified,
+    }
+}
+
+fn blob_sha(content: &[u8]) -> [u8; 20] {
+    let mut hasher = Sha1::new();
+    hasher.update(format!("blob {}\0", content.len()).as_bytes());
+    hasher.update(content);
+    hasher.digest().bytes()
+}
+
+#[derive(Debug)]
+struct IndexEntry {
+    path: String,
+    mtime: (u32, u32),
+    mode: u32,
+    size: u32,
+    sha: [u8; 20],
+    /// Anything but 0 is one side of a merge conflict
+    stage: u16,
+}
+
+fn read_index(path: &Path) -> Option<Vec<IndexEntry>> {
+    parse_index(&fs::read(path).ok()?)
+}
+
+/// Parses the entries of an index, versions 2 to 4 are supported
+fn parse_index(data: &[u8]) -> Option<Vec<IndexEntry>> {
+    if data.get(..4)? != b"DIRC" {
+        return None;
+    }
+    let version = be32(data, 4)?;
+    let count = be32(data, 8)?;
+    let mut entries = vec![];
+    let mut position = 12;
+    let mut previous_name: Vec<u8> = vec![];
+    for _ in 0..count {
+        let flags = u16::from_be_bytes(data.get(position + 60..position + 62)?.try_into().ok()?);
+        let mut name_start = position + 62;
+        if version >= 3 && flags & 0x4000 != 0 {
+            name_start += 2;
+        }
+        let (name, next) = if version >= 4 {
+            // The name replaces the end of the previous one
+            let mut at = name_start;
+            let strip = varint(data, &mut at)?;
+            let end = at + data.get(at..)?.iter().position(|&c| c == 0)?;
+            let mut name = previous_name
+                .get(..previous_name.len().checked_sub(strip)?)?
+                .to_vec();
+            name.extend_from_slice(&data[at..end]);
+            (name, end + 1)
+        } else {
+            let end = name_start + data.get(name_start..)?.iter().position(|&c| c == 0)?;
+            // Entries are padded with at least one null byte to a multiple of eight
+            let length = (end - position + 8) / 8 * 8;
+            (data[name_start..end].to_vec(), position + length)
+        };
+
+        entries.push(IndexEntry {
+            path: String::from_utf8_lossy(&name).to_string(),
+            mtime: (be32(data, position + 8)?, be32(data, position + 12)?),
+            mode: be32(data, position + 24)?,
+            size: be32(data, position + 36)?,
+            sha: data.get(position + 40..position + 60)?.try_into().ok()?,
+            stage: (flags >> 12) & 3,
+        });
+
+        position = next;
+        previous_name = name;
+    }
+    Some(entries)
+}
+
+fn be32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// The variable length numbers of index names and offset deltas, where every continuation adds
+/// one so there is only a single way to write each number
+fn varint(data: &[u8], at: &mut usize) -> Option<usize> {
+    let mut c = *data.get(*at)?;
+    *at += 1;
+    let mut value = (c & 0x7f) as usize;
+    while c & 0x80 != 0 {
+        c = *data.get(*at)?;
+        *at += 1;
+        value = ((value + 1) << 7) | (c & 0x7f) as usize;
+    }
+    Some(value)
+}
+
+fn from_hex(hex: &[u8]) -> Option<[u8; 20]> {
+    let mut sha = [0; 20];
+    for (i, byte) in sha.iter_mut().enumerate() {
+        let pair = std::str::from_utf8(hex.get(i * 2..i * 2 + 2)?).ok()?;
+        *byte = u8::from_str_radix(pair, 16).ok()?;
+    }
+    Some(sha)
+}
+
+fn to_hex(sha: &[u8; 20]) -> String {
+    sha.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The commit HEAD points to, None on a branch without commits
+fn head_commit(git_dir: &Path, common_dir: &Path) -> Option<[u8; 20]> {
+    let mut reference = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    // Symbolic references can point to each other, but not forever
+    for _ in 0..5 {
+        let Some(name) = reference.trim().strip_prefix("ref:") else {
+            return from_hex(reference.trim().as_bytes());
+        };
+        let name = name.trim().to_string();
+        reference = match fs::read_to_string(common_dir.join(&name))
+            .or_else(|_| fs::read_to_string(git_dir.join(&name)))
+        {
+            Ok(reference) => reference,
+            Err(_) => {
+                let packed = fs::read_to_string(common_dir.join("packed-refs")).ok()?;
+                return packed.lines().find_map(|line| {
+                    let (sha, packed_name) = line.split_once(' ')?;
+                    (packed_name == name).then(|| from_hex(sha.as_bytes()))?
+                });
+            }
+        };
+    }
+    None
+}
+
+/// Collects every file in the tree with its mode and blob
+fn read_tree(
+    objects: &Objects,
+    sha: &[u8; 20],
+    prefix: &str,
+    files: &mut BTreeMap<String, (u32, [u8; 20])>,
+) {
+    let Some((_, tree)) = objects.read(sha) else {
+        return;
+    };
+    let mut rest = tree.as_slice();
+    while let Some(space) = rest.iter().position(|&c| c == b' ') {
+        let Some(nul) = rest.iter().position(|&c| c == 0) else {
+            return;
+        };
+        let Some(entry_sha) = rest.get(nul + 1..nul + 21) else {
+            return;
+        };
+        let entry_sha: [u8; 20] = entry_sha.try_into().unwrap();
+        let mode = std::str::from_utf8(&rest[..space])
+            .ok()
+            .and_then(|mode| u32::from_str_radix(mode, 8).ok())
+            .unwrap_or_default();
+        let path = format!(
+            "{}{}",
+            prefix,
+            String::from_utf8_lossy(&rest[space + 1..nul])
+        );
+        if mode == 0o40000 {
+            read_tree(objects, &entry_sha, &format!("{}/", path), files);
+        } else {
+            files.insert(path, (mode, entry_sha));
+        }
+        rest = &rest[nul + 21..];
+    }
+}
+
+/// The object database, both loose objects and packs
+struct Objects {
+    directory: PathBuf,
+    /// The index of every pack and the path of the pack itself
+    packs: Vec<(Vec<u8>, PathBuf)>,
+}
+
+const OBJ_OFS_DELTA: u8 = 6;
+const OBJ_REF_DELTA: u8 = 7;
+
+impl Objects {
+    fn new(directory: &Path) -> Objects {
+        let packs = fs::read_dir(directory.join("pack"))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "idx"))
+            .filter_map(|index| Some((fs::read(&index).ok()?, index.with_extension("pack"))))
+            .collect();
+        Objects {
+            directory: directory.to_path_buf(),
+            packs,
+        }
+    }
+
+    /// The type and content of an object
+    fn read(&self, sha: &[u8; 20]) -> Option<(u8, Vec<u8>)> {
+        let hex = to_hex(sha);
+        if let Ok(file) = File::open(self.directory.join(&hex[..2]).join(&hex[2..])) {
+            let mut data = vec![];
+            ZlibDecoder::new(file).read_to_end(&mut data).ok()?;
+            let nul = data.iter().position(|&c| c == 0)?;
+            let kind = match data.split(|&c| c == b' ').next()? {
+                b"commit" => 1,
+                b"tree" => 2,
+                b"blob" => 3,
+                _ => 4,
+            };
+            return Some((kind, data[nul + 1..].to_vec()));
+        }
+        self.packs.iter().find_map(|(index, pack)| {
+            let offset = pack_offset(index, sha)?;
+            self.read_packed(&mut File::open(pack).ok()?, offset)
+        })
+    }
+
+    fn read_packed(&self, pack: &mut File, offset: u64) -> Option<(u8, Vec<u8>)> {
+        pack.seek(SeekFrom::Start(offset)).ok()?;
+        let mut reader = BufReader::new(&*pack);
+        let mut byte = [0; 1];
+        reader.read_exact(&mut byte).ok()?;
+        let kind = (byte[0] >> 4) & 7;
+        let mut size = (byte[0] & 0x0f) as usize;
+        let mut shift = 4;
+        while byte[0] & 0x80 != 0 {
+            reader.read_exact(&mut byte).ok()?;
+            size |= ((byte[0] & 0x7f) as usize) << shift;
+            shift += 7;
+        }
+
+        let base = match kind {
+            OBJ_OFS_DELTA => {
+                let mut encoded = vec![];
+                loop {
+                    reader.read_exact(&mut byte).ok()?;
+                    encoded.push(byte[0]);
+                    if byte[0] & 0x80 == 0 {
+                        break;
+                    }
+                }
+                Some(Err(offset.checked_sub(varint(&encoded, &mut 0)? as u64)?))
+            }
+            OBJ_REF_DELTA => {
+                let mut base = [0; 20];
+                reader.read_exact(&mut base).ok()?;
+                Some(Ok(base))
+            }
+            _ => None,
+        };
+        let mut data = Vec::with_capacity(size);
+        ZlibDecoder::new(reader)
+            .take(size as u64)
+            .read_to_end(&mut data)
+            .ok()?;
+
+        match base {
+            None => Some((kind, data)),
+            Some(base) => {
+                let (kind, base) = match base {
+                    Err(offset) => self.read_packed(pack, offset)?,
+                    Ok(sha) => self.read(&sha)?,
+                };
+                Some((kind, apply_delta(&base, &data)?))
+            }
+        }
+    }
+}
+
+/// Looks the object up in a version 2 pack index
+fn pack_offset(index: &[u8], sha: &[u8; 20]) -> Option<u64> {
+    if index.get(..8)? != b"\xfftOc\0\0\0\x02" {
+        return None;
+    }
+    let fanout = |i: usize| be32(index, 8 + i * 4).map(|n| n as usize);
+    let count = fanout(255)?;
+    let first = sha[0] as usize;
+    let start = if first == 0 { 0 } else { fanout(first - 1)? };
+    let end = fanout(first)?;
+    let names = 8 + 256 * 4;
+    let mut range = start..end;
+    let found = loop {
+        if range.is_empty() {
+            return None;
+        }
+        let middle = (range.start + range.end) / 2;
+        let name = index.get(names + middle * 20..names + middle * 20 + 20)?;
+        match name.cmp(sha.as_slice()) {
+            std::cmp::Ordering::Equal => break middle,
+            std::cmp::Ordering::Less => range.start = middle + 1,
+            std::cmp::Ordering::Greater => range.end = middle,
+        }
+    };
+    let offsets = names + count * 24;
+    let offset = be32(index, offsets + found * 4)?;
+    if offset & 0x8000_0000 == 0 {
+        return Some(offset as u64);
+    }
+    // Packs larger than 2 GiB keep the offsets which don't fit in another table
+    let large = offsets + count * 4 + (offset & 0x7fff_ffff) as usize * 8;
+    Some(u64::from_be_bytes(
+        index.get(large..large + 8)?.try_into().ok()?,
+    ))
+}
+
+/// Rebuilds an object from its base and the copy and insert instructions of the delta
+fn apply_delta(base: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let mut position = 0;
+    let mut size = || {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let c = *delta.get(position)?;
+            position += 1;
+            value |= ((c & 0x7f) as usize) << shift;
+            shift += 7;
+            if c & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+    };
+    let _base_size = size()?;
+    let result_size = size()?;
+    let mut result = Vec::with_capacity(result_size);
+    while let Some(&instruction) = delta.get(position) {
+        position += 1;
+        if instruction & 0x80 != 0 {
+            let mut offset = 0;
+            let mut length = 0;
+            for i in 0..4 {
+                if instruction & (1 << i) != 0 {
+                    offset |= (*delta.get(position)? as usize) << (i * 8);
+                    position += 1;
+                }
+            }
+            for i in 0..3 {
+                if instruction & (0x10 << i) != 0 {
+                    length |= (*delta.get(position)? as usize) << (i * 8);
+                    position += 1;
+                }
+            }
+            if length == 0 {
+                length = 0x10000;
+            }
+            result.extend_from_slice(base.get(offset..offset + length)?);
+        } else if instruction != 0 {
+            let length = instruction as usize;
+            result.extend_from_slice(delta.get(position..position + length)?);
+            position += length;
+        } else {
+            return None;
+        }
+    }
+    (result.len() == result_size).then_some(result)
+}
+
+/// The ignore rules of a repository: the global excludes file, .git/info/exclude and the
+/// .gitignore files, which are read as they are needed
+#[derive(Debug)]
+struct Ignore {
+    workdir: PathBuf,
+    /// Rules which apply to the whole repository
+    global: Vec<Pattern>,
+    /// The rules of the .gitignore in every directory, relative to the repository
+    directories: RefCell<HashMap<String, Rc<Vec<Pattern>>>>,
+}
+
+#[derive(Debug)]
+struct Pattern {
+    regex: Regex,
+    negated: bool,
+    directory_only: bool,
+    /// Patterns with a slash match the path from the directory of their .gitignore, the others
+    /// just the file name
+    anchored: bool,
+    /// The directory of the .gitignore, empty for the root of the repository
+    base: String,
+}
+
+impl Ignore {
+    fn new(workdir: &Path, git_dir: &Path) -> Ignore {
+        let config_home = var("XDG_CONFIG_HOME")
+            .ok()
+            .filter(|home| !home.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                var("HOME")
+                    .ok()
+                    .map(|home| Path::new(&home).join(".config"))
+            });
+        // core.excludesFile replaces the default global excludes file
+        let global_file = excludes_file(config_home.as_deref(), git_dir)
+            .or_else(|| config_home.map(|config| config.join("git/ignore")));
+        let mut global = vec![];
+        for file in global_file
+            .into_iter()
+            .chain([git_dir.join("info/exclude")])
+        {
+            global.extend(read_patterns(&file, ""));
+        }
+        Ignore {
+            workdir: workdir.to_path_buf(),
+            global,
+            directories: RefCell::default(),
+        }
+    }
+
+    /// Whether the path is ignored. Like git nothing in an ignored directory can be brought back
+    /// by a later rule
+    fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        let mut parent = String::new();
+        for component in path.split('/').collect::<Vec<_>>().split_last().unwrap().1 {
+            if !parent.is_empty() {
+                parent.push('/');
+            }
+            parent += component;
+            if self.matches(&parent, true) {
+                return true;
+            }
+        }
+        self.matches(path, is_dir)
+    }
+
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        let mut ignored = self.check(&self.global, path, is_dir);
+        // Deeper .gitignore files override the ones further up
+        let mut directory = String::new();
+        let directories = path.split('/').collect::<Vec<_>>();
+        for component in [""].iter().chain(&directories[..directories.len() - 1]) {
+            if !component.is_empty() {
+                if !directory.is_empty() {
+                    directory.push('/');
+                }
+                directory += component;
+            }
+            let patterns = self
+                .directories
+                .borrow_mut()
+                .entry(directory.clone())
+                .or_insert_with(|| {
+                    Rc::new(read_patterns(
+                        &self.workdir.join(&directory).join(".gitignore"),
+                        &directory,
+                    ))
+                })
+                .clone();
+            if let Some(result) = self.check(&patterns, path, is_dir) {
+                ignored = Some(result);
+            }
+        }
+        ignored.unwrap_or(false)
+    }
+
+    /// The result of the last pattern which matches, None if none do
+    fn check(&self, patterns: &[Pattern], path: &str, is_dir: bool) -> Option<bool> {
+        patterns.iter().rev().find_map(|pattern| {
+            if pattern.directory_only && !is_dir {
+                return None;
+            }
+            let relative = if pattern.base.is_empty() {
+                path
+            } else {
+                path.strip_prefix(&pattern.base)?.strip_prefix('/')?
+            };
+            let subject = if pattern.anchored {
+                relative
+            } else {
+                relative.rsplit('/').next().unwrap_or(relative)
+            };
+            pattern.regex.is_match(subject).then_some(!pattern.negated)
+        })
+    }
+}
+
+/// The core.excludesFile setting from the system, global and repository configuration, where the
+/// later ones win like in git. Only plain values are understood, includes are not followed
+fn excludes_file(config_home: Option<&Path>, git_dir: &Path) -> Option<PathBuf> {
+    let home = var("HOME").ok().map(PathBuf::from);
+    let files = [
+        Some(PathBuf::from("/etc/gitconfig")),
+        config_home.map(|config| config.join("git/config")),
+        home.as_ref().map(|home| home.join(".gitconfig")),
+        Some(git_dir.join("config")),
+    ];
+    let mut excludes_file = None;
+    for file in files.into_iter().flatten() {
+        let Ok(content) = fs::read_to_string(file) else {
+            continue;
+        };
+        let mut in_core = false;
+        for line in content.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_core = line.trim_end_matches(']')[1..]
+                    .trim()
+                    .eq_ignore_ascii_case("core");
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            if in_core && key.trim().eq_ignore_ascii_case("excludesfile") {
+                let value = value.trim().trim_matches('"');
+                excludes_file = Some(match (value.strip_prefix("~/"), &home) {
+                    (Some(rest), Some(home)) => home.join(rest),
+                    _ => PathBuf::from(value),
+                });
+            }
+        }
+    }
+    excludes_file
+}
+
+fn read_patterns(file: &Path, base: &str) -> Vec<Pattern> {
+    let Ok(content) = fs::read_to_string(file) else {
+        return vec![];
+    };
+    content
+        .lines()
+        .filter_map(|line| parse_pattern(line, base))
+        .collect()
+}
+
+fn parse_pattern(line: &str, base: &str) -> Option<Pattern> {
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    // Trailing spaces are ignored unless they are escaped
+    let bytes = line.as_bytes();
+    let mut end = bytes.len();
+    while end > 0 && bytes[end - 1] == b' ' && !(end > 1 && bytes[end - 2] == b'\\') {
+        end -= 1;
+    }
+    let mut pattern = &line[..end];
+    let negated = pattern.starts_with('!');
+    if negated {
+        pattern = &pattern[1..];
+    }
+    let directory_only = pattern.ends_with('/');
+    let pattern = pattern.trim_end_matches('/');
+    if pattern.is_empty() {
+        return None;
+    }
+    let anchored = pattern.contains('/');
+    let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+
+    Some(Pattern {
+        regex: Regex::new(&format!("^{}$", glob_to_regex(pattern))).ok()?,
+        negated,
+        directory_only,
+        anchored,
+        base: base.to_string(),
+    })
+}
+
+/// Translates the wildcards of a gitignore pattern into a regular expression
+fn glob_to_regex(glob: &str) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut regex = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*')
+                && (i == 0 || chars[i - 1] == '/')
+                && matches!(chars.get(i + 2), None | Some('/')) =>
+            {
+                // "**/" matches any number of directories, a trailing "/**" everything inside
+                if chars.get(i + 2).is_some() {
+                    regex += "(?:.*/)?";
+                    i += 3;
+                } else {
+                    regex += ".*";
+                    i += 2;
+                }
+                continue;
+            }
+            '*' => regex += "[^/]*",
+            '?' => regex += "[^/]",
+            '[' => match bracket_to_regex(&chars[i + 1..]) {
+                Some((class, length)) => {
+                    regex += &class;
+                    i += length + 1;
+                    continue;
+                }
+                None => regex += "\\[",
+            },
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                regex += &regex::escape(&chars[i].to_string());
+            }
+            c => regex += &regex::escape(&c.to_string()),
+        }
+        i += 1;
+    }
+    regex
+}
+
+/// Translates a bracket expression like "[!a-z]" or "[[:digit:]_]", starting after the '['.
+/// Returns the regex class and how many characters it took up, None if the bracket isn't closed
+/// and so is just a '['
+fn bracket_to_regex(chars: &[char]) -> Option<(String, usize)> {
+    let mut class = String::from("[");
+    let mut i = 0;
+    if matches!(chars.first(), Some('!' | '^')) {
+        // Like git, even a negated class doesn't match slashes
+        class += "^/";
+        i += 1;
+    }
+    let start = i;
+    loop {
+        match *chars.get(i)? {
+            // A ']' right at the start is part of the class
+            ']' if i > start => return Some((class + "]", i + 1)),
+            // POSIX classes like [:alpha:], which the regex crate understands as well. Names it
+            // doesn't know fail to compile, and git doesn't match anything with those either
+            '[' if chars.get(i + 1) == Some(&':') => {
+                let name = &chars[i + 2..];
+                match name.windows(2).position(|end| end == [':', ']']) {
+                    Some(length) => {
+                        class += "[:";
+                        class.extend(&name[..length]);
+                        class += ":]";
+                        i += length + 4;
+                    }
+                    None => {
+                        class += "\\[";
+                        i += 1;
+                    }
+                }
+            }
+            c => {
+                let (c, length) = match c {
+                    '\\' => (*chars.get(i + 1)?, 2),
+                    c => (c, 1),
+                };
+                class += &regex::escape(&c.to_string());
+                i += length;
+                // A '-' between two characters makes a range, otherwise it is just a dash
+                if chars.get(i) == Some(&'-') && chars.get(i + 1).is_some_and(|&c| c != ']') {
+                    class.push('-');
+                    i += 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The fixed part of an index entry, everything up to the name
+    fn entry_header(mode: u32, size: u32, sha: u8, stage: u16, name_length: usize) -> Vec<u8> {
+        let mut header = vec![0; 8];
+        header.extend_from_slice(&7u32.to_be_bytes());
+        header.extend_from_slice(&9u32.to_be_bytes());
+        header.extend_from_slice(&[0; 8]);
+        header.extend_from_slice(&mode.to_be_bytes());
+        header.extend_from_slice(&[0; 8]);
+        header.extend_from_slice(&size.to_be_bytes());
+        header.extend_from_slice(&[sha; 20]);
+        header.extend_from_slice(&((stage << 12) | name_length as u16).to_be_bytes());
+        header
+    }
+
+    fn index_header(version: u32, count: u32) -> Vec<u8> {
+        let mut index = b"DIRC".to_vec();
+        index.extend_from_slice(&version.to_be_bytes());
+        index.extend_from_slice(&count.to_be_bytes());
+        index
+    }
+
+    #[test]
+    fn index_version_2() {
+        let mut index = index_header(2, 2);
+        for (name, stage) in [("README", 0), ("src/main.rs", 2)] {
+            let start = index.len();
+            index.extend(entry_header(0o100644, 42, 0xab, stage, name.len()));
+            index.extend_from_slice(name.as_bytes());
+            // Padded with one to eight null bytes
+            let length = (index.len() - start + 8) / 8 * 8;
+            index.resize(start + length, 0);
+        }
+        let entries = parse_index(&index).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, "README");
+        assert_eq!(entries[0].mtime, (7, 9));
+        assert_eq!(entries[0].mode, 0o100644);
+        assert_eq!(entries[0].size, 42);
+        assert_eq!(entries[0].sha, [0xab; 20]);
+        assert_eq!(entries[0].stage, 0);
+        assert_eq!(entries[1].path, "src/main.rs");
+        assert_eq!(entries[1].stage, 2);
+    }
+
+    #[test]
+    fn index_version_4_prefix_compression() {
+        let mut index = index_header(4, 3);
+        // Each name drops bytes from the end of the previous one and adds its own
+        for (strip, suffix) in [(0, "src/lib.rs"), (6, "main.rs"), (7, "ls/mod.rs")] {
+            index.extend(entry_header(0o100755, 1, 1, 0, 0));
+            index.push(strip);
+            index.extend_from_slice(suffix.as_bytes());
+            index.push(0);
+        }
+        let paths: Vec<String> = parse_index(&index)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+        assert_eq!(paths, ["src/lib.rs", "src/main.rs", "src/ls/mod.rs"]);
+    }
+
+    #[test]
+    fn index_rejects_other_files() {
+        assert!(parse_index(b"PACK\0\0\0\x02").is_none());
+        // An entry which is cut short
+        let mut index = index_header(2, 1);
+        index.extend(entry_header(0o100644, 0, 0, 0, 4)[..30].to_vec());
+        assert!(parse_index(&index).is_none());
+    }
+
+    #[test]
+    fn varint_adds_one_per_continuation() {
+        let mut at = 0;
+        assert_eq!(varint(&[0x05], &mut at), Some(5));
+        assert_eq!(at, 1);
+        at = 0;
+        assert_eq!(varint(&[0x80, 0x00], &mut at), Some(128));
+        at = 0;
+        assert_eq!(varint(&[0x81, 0x7f], &mut at), Some(((1 + 1) << 7) | 0x7f));
+        at = 0;
+        assert_eq!(varint(&[0x80], &mut at), None);
+    }
+
+    #[test]
+    fn delta_copies_and_inserts() {
+        let base = b"hello world";
+        let mut delta = vec![11, 17];
+        // Copy "hello " from offset 0
+        delta.extend_from_slice(&[0x90, 6]);
+        // Insert "there "
+        delta.push(6);
+        delta.extend_from_slice(b"there ");
+        // Copy "world" from offset 6
+        delta.extend_from_slice(&[0x91, 6, 5]);
+        assert_eq!(
+            apply_delta(base, &delta).as_deref(),
+            Some(&b"hello there world"[..])
+        );
+
+        // The result has to have the announced size
+        delta[1] = 18;
+        assert!(apply_delta(base, &delta).is_none());
+        // Copies can't reach past the base
+        assert!(apply_delta(base, &[11, 5, 0x91, 8, 5]).is_none());
+    }
+
+    #[test]
+    fn pack_index_offsets() {
+        let shas = [[0x01; 20], [0xab; 20]];
+        let mut index = b"\xfftOc\0\0\0\x02".to_vec();
+        for i in 0..256 {
+            let count = shas.iter().filter(|sha| sha[0] as usize <= i).count() as u32;
+            index.extend_from_slice(&count.to_be_bytes());
+        }
+        for sha in &shas {
+            index.extend_from_slice(sha);
+        }
+        // The checksums aren't used
+        index.extend_from_slice(&[0; 8]);
+        index.extend_from_slice(&12u32.to_be_bytes());
+        // The second offset is in the table of large offsets
+        index.extend_from_slice(&0x8000_0000u32.to_be_bytes());
+        index.extend_from_slice(&0x1_0000_0000u64.to_be_bytes());
+
+        assert_eq!(pack_offset(&index, &[0x01; 20]), Some(12));
+        assert_eq!(pack_offset(&index, &[0xab; 20]), Some(0x1_0000_0000));
+        assert_eq!(pack_offset(&index, &[0x02; 20]), None);
+        assert_eq!(pack_offset(&index[4..], &[0x01; 20]), None);
+    }
+
+    /// The result of the last of the patterns which matches the path
+    fn check(patterns: &[&str], base: &str, path: &str, is_dir: bool) -> Option<bool> {
+        let patterns: Vec<Pattern> = patterns
+            .iter()
+            .filter_map(|pattern| parse_pattern(pattern, base))
+            .collect();
+        ignore(&[]).check(&patterns, path, is_dir)
+    }
+
+    /// Rules without any .gitignore files, the worktree doesn't exist
+    fn ignore(global: &[&str]) -> Ignore {
+        Ignore {
+            workdir: PathBuf::from("/nonexistent/blutils"),
+            global: global
+                .iter()
+                .filter_map(|pattern| parse_pattern(pattern, ""))
+                .collect(),
+            directories: RefCell::default(),
+        }
+    }
+
+    #[test]
+    fn patterns_skip_comments_and_blank_lines() {
+        assert!(parse_pattern("", "").is_none());
+        assert!(parse_pattern("# comment", "").is_none());
+        assert!(parse_pattern("/", "").is_none());
+        assert_eq!(check(&["\\#hash"], "", "#hash", false), Some(true));
+    }
+
+    #[test]
+    fn patterns_any_directory() {
+        assert_eq!(check(&["**/foo"], "", "foo", false), Some(true));
+        assert_eq!(check(&["**/foo"], "", "a/b/foo", false), Some(true));
+        assert_eq!(check(&["a/**/b"], "", "a/b", false), Some(true));
+        assert_eq!(check(&["a/**/b"], "", "a/x/y/b", false), Some(true));
+        assert_eq!(check(&["a/**/b"], "", "c/a/x/b", false), None);
+        assert_eq!(check(&["a/**"], "", "a/x/y", false), Some(true));
+        assert_eq!(check(&["a/**"], "", "a", true), None);
+    }
+
+    #[test]
+    fn patterns_directory_only() {
+        assert_eq!(check(&["build/"], "", "build", true), Some(true));
+        assert_eq!(check(&["build/"], "", "build", false), None);
+        // Without another slash the name matches at any depth
+        assert_eq!(check(&["build/"], "", "src/build", true), Some(true));
+        assert_eq!(check(&["/build/"], "", "src/build", true), None);
+    }
+
+    #[test]
+    fn patterns_negation_last_match_wins() {
+        let patterns = ["*.log", "!keep.log"];
+        assert_eq!(check(&patterns, "", "debug.log", false), Some(true));
+        assert_eq!(check(&patterns, "", "logs/keep.log", false), Some(false));
+        assert_eq!(check(&patterns, "", "main.rs", false), None);
+        assert_eq!(
+            check(&["!keep.log", "*.log"], "", "keep.log", false),
+            Some(true)
+        );
+        assert_eq!(check(&["\\!bang"], "", "!bang", false), Some(true));
+    }
+
+    #[test]
+    fn patterns_character_classes() {
+        assert_eq!(check(&["[!a]x"], "", "bx", false), Some(true));
+        assert_eq!(check(&["[!a]x"], "", "ax", false), None);
+        assert_eq!(check(&["[a-c].rs"], "", "b.rs", false), Some(true));
+        assert_eq!(check(&["[a-c].rs"], "", "d.rs", false), None);
+        assert_eq!(check(&["?.rs"], "", "a.rs", false), Some(true));
+        assert_eq!(check(&["?.rs"], "", "ab.rs", false), None);
+        // Wildcards don't match slashes
+        assert_eq!(check(&["src/*.rs"], "", "src/ls/mod.rs", false), None);
+    }
+
+    #[test]
+    fn patterns_posix_classes() {
+        assert_eq!(check(&["[[:digit:]]*"], "", "2024.log", false), Some(true));
+        assert_eq!(check(&["[[:digit:]]*"], "", "a.log", false), None);
+        assert_eq!(check(&["x[[:alpha:]_]"], "", "x_", false), Some(true));
+        assert_eq!(check(&["x[[:alpha:]_]"], "", "xq", false), Some(true));
+        assert_eq!(check(&["x[[:alpha:]_]"], "", "x1", false), None);
+        assert_eq!(check(&["[![:upper:]]"], "", "a", false), Some(true));
+        assert_eq!(check(&["[![:upper:]]"], "", "A", false), None);
+        // A ']' at the start is part of the class, a '-' at the end is just a dash
+        assert_eq!(check(&["[]a]"], "", "]", false), Some(true));
+        assert_eq!(check(&["[a-]"], "", "-", false), Some(true));
+        assert_eq!(check(&["[a-]"], "", "b", false), None);
+        // Unknown classes don't match anything, just like in git
+        assert_eq!(check(&["[[:nope:]]"], "", "a", false), None);
+        assert_eq!(check(&["a[b"], "", "a[b", false), Some(true));
+    }
+
+    #[test]
+    fn patterns_anchoring_and_spaces() {
+        assert_eq!(check(&["/root.txt"], "", "root.txt", false), Some(true));
+        assert_eq!(check(&["/root.txt"], "", "sub/root.txt", false), None);
+        // Patterns of a nested .gitignore are relative to its directory
+        assert_eq!(check(&["/x.tmp"], "sub", "sub/x.tmp", false), Some(true));
+        assert_eq!(check(&["*.tmp"], "sub", "x.tmp", false), None);
+        // Trailing spaces are dropped unless they are escaped
+        assert_eq!(check(&["name  "], "", "name", false), Some(true));
+        assert_eq!(check(&["name\\ "], "", "name ", false), Some(true));
+    }
+
+    #[test]
+    fn ignored_directories_hide_everything_in_them() {
+        let ignore = ignore(&["target/", "!target/keep"]);
+        assert!(ignore.is_ignored("target", true));
+        assert!(ignore.is_ignored("target/keep", false));
+        assert!(ignore.is_ignored("target/debug/build", false));
+        assert!(!ignore.is_ignored("src/target", false));
+        assert!(!ignore.is_ignored("src/main.rs", false));
+    }
+}
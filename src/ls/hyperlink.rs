@@ -0,0 +1,63 @@
+//! OSC 8 hyperlinks around entry names, which lets terminals open the files from the listing
+
+use std::{
+    ffi::CStr,
+    fs::canonicalize,
+    os::unix::ffi::OsStrExt,
+    path::{absolute, Path, PathBuf},
+};
+
+/// The host name which goes into the file:// URIs, empty if it can't be looked up
+pub fn host_name() -> String {
+    let mut buffer = [0u8; 256];
+    // SAFETY: the buffer is valid for its whole length, and the last byte is never written so the
+    // name stays null terminated even if it got truncated
+    let result =
+        unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len() - 1) };
+    if result != 0 {
+        return String::new();
+    }
+    CStr::from_bytes_until_nul(&buffer)
+        .map(|name| escape(name.to_bytes(), false))
+        .unwrap_or_default()
+}
+
+/// Wraps the text in a link to the path. Like GNU the link points to the canonical path, so links
+/// to symlinks open whatever they point to
+pub fn link(host: &str, path: &Path, text: &[u8]) -> Vec<u8> {
+    let uri = escape(canonical(path).as_os_str().as_bytes(), true);
+    [
+        format!("\x1b]8;;file://{}{}\x07", host, uri).as_bytes(),
+        text,
+        b"\x1b]8;;\x07",
+    ]
+    .concat()
+}
+
+/// Resolves every symlink in the path, or as much of it as exists if it is a broken link
+fn canonical(path: &Path) -> PathBuf {
+    if let Ok(path) = canonicalize(path) {
+        return path;
+    }
+    match (path.parent().map(canonicalize), path.file_name()) {
+        (Some(Ok(parent)), Some(name)) => parent.join(name),
+        _ => absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+    }
+}
+
+/// Percent-encodes everything except for the unreserved characters of RFC 3986, and slashes
+/// when escaping a path
+fn escape(bytes: &[u8], path: bool) -> String {
+    let mut result = String::with_capacity(bytes.len());
+    for &c in bytes {
+        if c.is_ascii_alphanumeric()
+            || matches!(c, b'-' | b'.' | b'_' | b'~')
+            || (path && c == b'/')
+        {
+            result.push(c as char);
+        } else {
+            result += &format!("%{:02x}", c);
+        }
+    }
+    result
+}
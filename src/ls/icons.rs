@@ -0,0 +1,203 @@
+//! Nerd Font icons in front of entry names for --icons. The built-in table can be changed in
+//! $XDG_CONFIG_HOME/blutils/icons, one "KEY=ICON" per line. Keys are the file type keys of
+//! LS_COLORS like "di", "*.ext" for extensions or the whole file name
+
+use std::{
+    collections::HashMap,
+    env::var,
+    fs::{read_to_string, Metadata},
+    path::PathBuf,
+};
+
+use crate::utils::PathType;
+
+/// Icons of the file types, under the same keys as in LS_COLORS
+const TYPE_ICONS: [(&str, &str); 7] = [
+    ("fi", "\u{f15b}"),
+    ("di", "\u{f07b}"),
+    ("ln", "\u{f0c1}"),
+    ("ex", "\u{f489}"),
+    ("pi", "\u{f0ec}"),
+    ("so", "\u{f1e6}"),
+    ("do", "\u{f090}"),
+];
+
+const EXTENSION_ICONS: [(&str, &str); 58] = [
+    ("7z", "\u{f410}"),
+    ("bash", "\u{f489}"),
+    ("bmp", "\u{f1c5}"),
+    ("bz2", "\u{f410}"),
+    ("c", "\u{e61e}"),
+    ("cc", "\u{e61d}"),
+    ("conf", "\u{e615}"),
+    ("cpp", "\u{e61d}"),
+    ("css", "\u{e749}"),
+    ("csv", "\u{f1c3}"),
+    ("deb", "\u{f187}"),
+    ("diff", "\u{f440}"),
+    ("doc", "\u{f1c2}"),
+    ("docx", "\u{f1c2}"),
+    ("flac", "\u{f001}"),
+    ("gif", "\u{f1c5}"),
+    ("go", "\u{e626}"),
+    ("gz", "\u{f410}"),
+    ("h", "\u{f0fd}"),
+    ("hpp", "\u{f0fd}"),
+    ("html", "\u{f13b}"),
+    ("ini", "\u{e615}"),
+    ("iso", "\u{f1c0}"),
+    ("java", "\u{e256}"),
+    ("jpeg", "\u{f1c5}"),
+    ("jpg", "\u{f1c5}"),
+    ("js", "\u{e74e}"),
+    ("json", "\u{e60b}"),
+    ("lock", "\u{f023}"),
+    ("log", "\u{f18d}"),
+    ("lua", "\u{e620}"),
+    ("md", "\u{f48a}"),
+    ("mkv", "\u{f03d}"),
+    ("mp3", "\u{f001}"),
+    ("mp4", "\u{f03d}"),
+    ("ogg", "\u{f001}"),
+    ("patch", "\u{f440}"),
+    ("pdf", "\u{f1c1}"),
+    ("png", "\u{f1c5}"),
+    ("py", "\u{e606}"),
+    ("rb", "\u{e21e}"),
+    ("rpm", "\u{f187}"),
+    ("rs", "\u{e7a8}"),
+    ("sh", "\u{f489}"),
+    ("sql", "\u{f1c0}"),
+    ("svg", "\u{f1c5}"),
+    ("tar", "\u{f410}"),
+    ("toml", "\u{e615}"),
+    ("ts", "\u{e628}"),
+    ("txt", "\u{f15c}"),
+    ("vim", "\u{e62b}"),
+    ("wav", "\u{f001}"),
+    ("webm", "\u{f03d}"),
+    ("xml", "\u{f05c0}"),
+    ("xz", "\u{f410}"),
+    ("yaml", "\u{f481}"),
+    ("yml", "\u{f481}"),
+    ("zip", "\u{f410}"),
+];
+
+/// Well-known files, which get their icon no matter their extension
+const NAME_ICONS: [(&str, &str); 16] = [
+    (".git", "\u{e5fb}"),
+    (".gitattributes", "\u{f1d3}"),
+    (".github", "\u{e5fd}"),
+    (".gitignore", "\u{f1d3}"),
+    (".gitmodules", "\u{f1d3}"),
+    ("Cargo.lock", "\u{e7a8}"),
+    ("Cargo.toml", "\u{e7a8}"),
+    ("CMakeLists.txt", "\u{f489}"),
+    ("Dockerfile", "\u{f308}"),
+    ("GNUmakefile", "\u{f489}"),
+    ("LICENSE", "\u{f02d}"),
+    ("Makefile", "\u{f489}"),
+    ("README", "\u{f05a}"),
+    ("README.md", "\u{f05a}"),
+    ("makefile", "\u{f489}"),
+    ("node_modules", "\u{e718}"),
+];
+
+#[derive(Debug, Clone)]
+pub struct Icons {
+    types: HashMap<String, String>,
+    /// Extensions without the dot, in lowercase
+    extensions: HashMap<String, String>,
+    names: HashMap<String, String>,
+}
+
+impl Icons {
+    /// The built-in icons with the ones from the config file on top. Lines which can't be read
+    /// are skipped with a warning
+    pub fn load() -> Icons {
+        let to_map = |table: &[(&str, &str)]| {
+            table
+                .iter()
+                .map(|(key, icon)| (key.to_string(), icon.to_string()))
+                .collect()
+        };
+        let mut icons = Icons {
+            types: to_map(&TYPE_ICONS),
+            extensions: to_map(&EXTENSION_ICONS),
+            names: to_map(&NAME_ICONS),
+        };
+        let Some(path) = config_file() else {
+            return icons;
+        };
+        let Ok(config) = read_to_string(&path) else {
+            return icons;
+        };
+        for (number, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once('=') {
+                Some((key, icon)) if !key.is_empty() => icons.set(key, icon),
+                _ => eprintln!(
+                    "ls: {}:{}: invalid icon definition",
+                    path.display(),
+                    number + 1
+                ),
+            }
+        }
+        icons
+    }
+
+    fn set(&mut self, key: &str, icon: &str) {
+        let icon = icon.to_string();
+        if let Some(extension) = key.strip_prefix("*.") {
+            self.extensions.insert(extension.to_lowercase(), icon);
+        } else if TYPE_ICONS.iter().any(|(type_key, _)| *type_key == key) {
+            self.types.insert(key.to_string(), icon);
+        } else {
+            self.names.insert(key.to_string(), icon);
+        }
+    }
+
+    /// The icon of an entry. Well-known names go first, then the extension of regular files and
+    /// finally the type. Entries which couldn't be read get the icon of a file
+    pub fn get(&self, name: &str, metadata: Option<&Metadata>) -> &str {
+        let path_type = metadata.map_or(PathType::File, PathType::from_metadata);
+        if let Some(icon) = self.names.get(name) {
+            return icon;
+        }
+        if matches!(path_type, PathType::File | PathType::Executable) {
+            let extension = name
+                .rsplit_once('.')
+                .filter(|(stem, _)| !stem.is_empty())
+                .and_then(|(_, extension)| self.extensions.get(&extension.to_lowercase()));
+            if let Some(icon) = extension {
+                return icon;
+            }
+        }
+        let key = match path_type {
+            PathType::File => "fi",
+            PathType::Directory => "di",
+            PathType::Symlink => "ln",
+            PathType::Executable => "ex",
+            PathType::FIFO => "pi",
+            PathType::Socket => "so",
+            PathType::Door => "do",
+        };
+        self.types.get(key).map_or("", String::as_str)
+    }
+}
+
+fn config_file() -> Option<PathBuf> {
+    let config = var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|config| !config.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            var("HOME")
+                .ok()
+                .map(|home| PathBuf::from(home).join(".config"))
+        })?;
+    Some(config.join("blutils/icons"))
+}
@@ -0,0 +1,97 @@
+//! Machine-readable output for --json and --ndjson. Every entry becomes one object, either in a
+//! single array or on a line of its own
+
+use std::{cell::Cell, fmt};
+
+#[derive(Debug, Clone, Default)]
+pub struct JsonOutput {
+    /// One object per line instead of an array, for --ndjson
+    lines: bool,
+    /// Whether an object was printed yet, the ones after it need a comma in an array
+    printed: Cell<bool>,
+}
+
+impl JsonOutput {
+    pub fn new(lines: bool) -> JsonOutput {
+        JsonOutput {
+            lines,
+            printed: Cell::new(false),
+        }
+    }
+
+    pub fn begin(&self) {
+        if !self.lines {
+            print!("[");
+        }
+    }
+
+    pub fn print(&self, object: &Object) {
+        if self.lines {
+            println!("{}", object);
+            return;
+        }
+        let separator = if self.printed.replace(true) { "," } else { "" };
+        print!("{}\n  {}", separator, object);
+    }
+
+    pub fn end(&self) {
+        if !self.lines {
+            let newline = if self.printed.get() { "\n" } else { "" };
+            println!("{}]", newline);
+        }
+    }
+}
+
+/// A JSON object, fields are kept in the order they were added
+#[derive(Debug, Default)]
+pub struct Object {
+    fields: Vec<(&'static str, String)>,
+}
+
+impl Object {
+    /// Adds a string, or null if there is none
+    pub fn string(mut self, key: &'static str, value: Option<&str>) -> Object {
+        self.fields
+            .push((key, value.map_or(String::from("null"), quote)));
+        self
+    }
+
+    /// Adds a number, or null if there is none
+    pub fn number<T: fmt::Display>(mut self, key: &'static str, value: Option<T>) -> Object {
+        self.fields.push((
+            key,
+            value.map_or(String::from("null"), |value| value.to_string()),
+        ));
+        self
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{")?;
+        for (i, (key, value)) in self.fields.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(f, "{}{}:{}", separator, quote(key), value)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// A JSON string with quotes, backslashes and control characters escaped
+fn quote(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + 2);
+    result.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => result += "\\\"",
+            '\\' => result += "\\\\",
+            '\n' => result += "\\n",
+            '\r' => result += "\\r",
+            '\t' => result += "\\t",
+            c if (c as u32) < 0x20 || c == '\u{7f}' => result += &format!("\\u{:04x}", c as u32),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
@@ -0,0 +1,2517 @@
+use core::fmt;
+use libc::{ELOOP, S_IXGRP, S_IXOTH, S_IXUSR};
+use nix::{
+    dir::Dir,
+    fcntl::OFlag,
+    sys::stat::{major, minor, Mode},
+};
+use std::{
+    cell::{Cell, RefCell},
+    cmp::Ordering,
+    collections::HashMap,
+    env::{args_os, var},
+    ffi::{OsStr, OsString},
+    fmt::Debug,
+    fs::{read_link, Metadata},
+    io::{stdout, ErrorKind, IsTerminal, Write},
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{FileTypeExt, MetadataExt},
+    },
+    path::{Path, PathBuf},
+    process::exit,
+    str::FromStr,
+    time::UNIX_EPOCH,
+    usize,
+};
+
+use crate::utils::{filevercmp, BlockSize, DiskUsage, MetadataPlus, PermissionsPlus, Usage};
+
+use self::{
+    colors::LsColors,
+    dired::Dired,
+    git::Repositories,
+    icons::Icons,
+    json::{JsonOutput, Object},
+    quoting::quote,
+    time::TimeStyle,
+};
+
+use chrono::{DateTime, Local, SecondsFormat, TimeZone};
+
+use clap::{parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use regex::bytes::Regex;
+use unicode_width::UnicodeWidthStr;
+
+mod colors;
+mod dired;
+mod git;
+mod hyperlink;
+mod icons;
+mod json;
+mod quoting;
+mod time;
+
+const PROGRAM: &str = "ls";
+
+#[derive(Parser, Debug, Clone)]
+#[command(
+    version,
+    about = "List files in source!",
+    author = "Alexander Hübner",
+    disable_help_flag = true,
+    args_override_self = true
+)]
+struct Cli {
+    #[clap(value_parser, value_name("FILE"), default_values = ["."])]
+    files: Vec<PathBuf>,
+    /// Raised to 1 for minor problems and to 2 for operands which can't be accessed, like GNU
+    #[arg(skip)]
+    exit_status: Cell<i32>,
+    /// Whether a group of entries or a "dir:" header was printed yet. Every header after that is
+    /// preceded by a blank line
+    #[arg(skip)]
+    printed_section: Cell<bool>,
+
+    //Done
+    #[arg(
+        short = 'a',
+        long = "all",
+        help = "Do not ignore entries starting with ."
+    )]
+    all: bool,
+    //Done
+    #[arg(
+        short = 'A',
+        long = "almost-all",
+        help = "Same as all but does not list . and .."
+    )]
+    almost_all: bool,
+
+    // Done
+    #[arg(long = "author", help = "With -l, print the author of each file")]
+    author: bool,
+    // Done
+    #[arg(
+        short = 'b',
+        long = "escape",
+        help = "Print C-style escapes for nongraphic characters"
+    )]
+    print_escapes: bool,
+    // Done
+    #[arg(
+        long = "block-size",
+        help = "With -l, scale sizes by SIZE when printing them; e.g., '--block-size=M'. SIZE is an integer and optional unit, K, M, G, T, P, E, Z, Y, R, Q are powers of 1024 and KB, MB, ... powers of 1000. Without a number the unit is printed after the sizes. Defaults to LS_BLOCK_SIZE, BLOCK_SIZE or BLOCKSIZE from the environment",
+        value_name("SIZE")
+    )]
+    block_size: Option<BlockSize>,
+    /// What file sizes in long listings are scaled by
+    #[arg(skip)]
+    file_block_size: BlockSize,
+    /// What -s and the total line are scaled by
+    #[arg(skip)]
+    output_block_size: BlockSize,
+    // Done
+    #[arg(
+        short = 'B',
+        long = "ignore-backups",
+        help = "Do not list entries ending with ~ or a specified suffix",
+        value_name("suffix"),
+        num_args=0..=1,
+        default_missing_value("~")
+    )]
+    ignore_backups: Option<String>,
+    // Done
+    #[arg(
+        short = 'c',
+        help = "With -lt: sort by, and show, ctime (time of last change of file status information); with -l: show ctime and sort by name; otherwise: sort by ctime, newest first"
+    )]
+    sort_access_ctime: bool,
+    // Done
+    #[arg(short = 'C', help = "List entries by columns")]
+    column: bool,
+    #[arg(
+        long = "color",
+        help = "Color the output WHEN, defaults to auto. The colours are read from LS_COLORS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value("always")
+    )]
+    color: Option<When>,
+    /// Resolved from --color and LS_COLORS, None when the output isn't coloured
+    #[arg(skip)]
+    colors: Option<LsColors>,
+    // Done
+    #[arg(
+        short = 'd',
+        long = "directory",
+        help = "List directories themselves, not their contents"
+    )]
+    directory: bool,
+    // Done
+    #[arg(
+        short = 'D',
+        long = "dired",
+        help = "Generate output designed for Emacs' dired mode"
+    )]
+    dired: bool,
+    /// Set up when --dired applies, which is only to long listings without hyperlinks
+    #[arg(skip)]
+    dired_output: Option<Dired>,
+    // Done
+    #[arg(short = 'f', help = "Do not sort, enable -aU, disable -ls --color")]
+    no_sort_color: bool,
+    // Done
+    #[arg(
+        short = 'F',
+        long = "classify",
+        help = "Append indicator (one of */=>@|) to entries WHEN, defaults to always",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value("always"),
+        action = clap::ArgAction::Append
+    )]
+    classify: Vec<When>,
+    // Done
+    #[arg(long = "file-type", help = "Likewise, except do not append '*'")]
+    file_type: bool,
+    // Done
+    #[arg(
+        long = "format",
+        help = "Across -x, commas -m, horizontal -x, long -l, single-column -1, verbose -l, vertical -C"
+    )]
+    format: Option<FormatWord>,
+    // Done
+    #[arg(long = "full-time", help = "Like -l --time-style=full-iso")]
+    full_time: bool,
+    // Done
+    #[arg(short = 'g', help = "Like -l but does not list owner")]
+    list_no_owner: bool,
+    // Done
+    #[arg(
+        long = "git",
+        help = "With -l, print the git status of each file, staged and unstaged"
+    )]
+    git: bool,
+    // Done
+    #[arg(
+        long = "gitignore",
+        help = "Hide entries ignored by git: .gitignore files, .git/info/exclude and the global excludes file"
+    )]
+    gitignore: bool,
+    /// Set up when the git column is shown, which is only in long listings, or with --gitignore
+    #[arg(skip)]
+    git_repositories: Option<Repositories>,
+    // Done
+    #[arg(
+        long = "group-directories-first",
+        help = "Group directories before files; can be augmented with a --sort option, but any use of --sort=none (-U) disables grouping"
+    )]
+    group_directories_first: bool,
+    // Done
+    #[arg(
+        short = 'G',
+        long = "no-group",
+        help = "In a long listing, dont print group names"
+    )]
+    no_group: bool,
+    // Done
+    #[arg(
+        short = 'h',
+        long = "human-readable",
+        help = "With -l and -s, print sizes like 1K 234M 2G etc."
+    )]
+    human_readable: bool,
+    // Done
+    #[arg(
+        long = "si",
+        help = "Like human-readable but use powers of 1000, not 1024"
+    )]
+    human_readable_1000: bool,
+    // Done
+    #[arg(
+        short = 'H',
+        long = "dereference-command-line",
+        help = "Always dereference symbolic links passed as arguments"
+    )]
+    dereference_argument: bool,
+    // Done
+    #[arg(
+        long = "dereference-command-line-symlink-to-dir",
+        help = "Follow each command line symbolic link that points to a directory"
+    )]
+    dereference_argument_dir: bool,
+    // Done
+    #[arg(
+        long = "hide",
+        help = "Do not list entries which matches regex PATTERN, overriden by -a or -A",
+        value_name("PATTERN")
+    )]
+    hide: Vec<String>,
+    // Done
+    #[arg(
+        long = "hyperlink",
+        help = "Hyperlink file names WHEN, defaults to never",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value("always")
+    )]
+    hyperlink_when: Option<When>,
+    /// Resolved from --hyperlink, the host name for the links. None when names aren't linked
+    #[arg(skip)]
+    hyperlink_host: Option<String>,
+    // Done
+    #[arg(
+        long = "icons",
+        help = "Show icons in front of file names WHEN, defaults to never",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value("always")
+    )]
+    icons_when: Option<When>,
+    /// The icon table, only loaded when icons are shown
+    #[arg(skip)]
+    icons: Option<Icons>,
+    // Done
+    #[arg(long = "json", help = "Print the entries as one JSON array")]
+    json: bool,
+    // Done
+    #[arg(
+        long = "ndjson",
+        help = "Print every entry as a JSON object on a line of its own"
+    )]
+    ndjson: bool,
+    /// Set up with --json or --ndjson, which replace every other format
+    #[arg(skip)]
+    json_output: Option<JsonOutput>,
+    // Done
+    /// Resolved from -F, -p, --file-type and --indicator-style, whichever came last
+    #[arg(
+        long = "indicator-style",
+        help = "Append indicator with style WORD to entry names: none (default), slash (-p), file-type (--file-type), classify (-F)"
+    )]
+    indicator_style: Option<IndicatorWord>,
+    // Done
+    #[arg(
+        short = 'i',
+        long = "inode",
+        help = "Print the index number of each file"
+    )]
+    inode: bool,
+    // Done
+    #[arg(
+        short = 'I',
+        long = "ignore",
+        help = "Do not list entries which matches regex PATTERN",
+        value_name("PATTERN")
+    )]
+    ignore_pattern: Vec<String>,
+    // Done
+    #[arg(
+        short = 'k',
+        long = "kibibytes",
+        help = "Default to 1024-byte blocks for file system usage; used only with -s and directory totals"
+    )]
+    kibibytes: bool,
+    // Done
+    #[arg(
+        long = "level",
+        help = "Descend at most DEPTH levels with --tree or -R",
+        value_name("DEPTH")
+    )]
+    level: Option<usize>,
+    // Done
+    #[arg(short = 'l', help = "Use a long listing format")]
+    list: bool,
+    // Done
+    #[arg(
+        short = 'L',
+        long = "dereference",
+        help = "Use dereferenced symbolic link information in result instead of symbolic link itself"
+    )]
+    dereference: bool,
+    /// Resolved from -L, -H and --dereference-command-line-symlink-to-dir
+    #[arg(skip)]
+    dereference_mode: Dereference,
+    // Done
+    #[arg(
+        short = 'm',
+        help = "Fill width with a comma separated list of entries"
+    )]
+    fill_comma: bool,
+    // Done
+    #[arg(
+        short = 'n',
+        long = "numeric-uid-gid",
+        help = "Like l, but list numeric user and group IDs"
+    )]
+    numeric_list: bool,
+    // Done
+    #[arg(
+        short = 'N',
+        long = "literal",
+        help = "Print entry names without quoting"
+    )]
+    literal: bool,
+    // Done
+    #[arg(
+        short = 'o',
+        help = "Like -l but do not list group information - same as -lG"
+    )]
+    no_group_list: bool,
+    // Done
+    #[arg(short = 'p', help = "Append / to directories")]
+    slash: bool,
+    // Done
+    #[arg(
+        short = 'q',
+        long = "hide-control-chars",
+        help = "Print ? instead of nongraphic characters (the default on a terminal)"
+    )]
+    hide_control_chars: bool,
+    // Done
+    #[arg(
+        long = "show-control-chars",
+        help = "Show nongraphic as-is (No special visualization)"
+    )]
+    show_control_chars: bool,
+    // Done
+    #[arg(
+        short = 'Q',
+        long = "quote-name",
+        help = "Enclose entry names in double quotes"
+    )]
+    quote_name: bool,
+    // Done
+    #[arg(
+        long = "quoting-style",
+        help = "Use quoting style WORD for entry names: literal, locale, shell, shell-always, shell-escape, shell-escape-always, c, c-maybe, escape, clocale (overrides QUOTING_STYLE environment variable). Defaults to shell-escape on a terminal and literal otherwise",
+        value_name("WORD")
+    )]
+    quoting_style: Option<QuotingWord>,
+    // Done
+    #[arg(short = 'r', long = "reverse", help = "Reverse order while sorting")]
+    reverse: bool,
+    // Done
+    #[arg(
+        short = 'R',
+        long = "recursive",
+        help = "List subdirectories recursively"
+    )]
+    recursive: bool,
+    // Done
+    #[arg(
+        short = 's',
+        long = "size",
+        help = "Print the allocated size of each file, in blocks"
+    )]
+    size_blocks: bool,
+    // Done
+    #[arg(short = 'S', help = "Sort by file size, largest first")]
+    size_sort: bool,
+    // Done
+    #[arg(
+        long = "sort",
+        help = "Sort by WORD instead of name: none (-U), size (-S), time (-t), version (-v), extension (-X), width, name"
+    )]
+    sort_word: Option<SortWord>,
+    // Done
+    #[arg(
+        long = "time",
+        help = "Select which timestamp used to display or sort; access time (-u): atime, access, use; metadata change time (-c): ctime, status;  modified  time  (default): mtime, modification; birth time: birth, creation;\nWith -l, WORD determines which time to show; with --sort=time, sort by WORD (newest first)",
+        value_name("WORD")
+    )]
+    time_display_sort: Option<TimeWord>,
+    // Done
+    #[arg(
+        long = "time-style",
+        help = "Time/Date format of -l; TIME_STYLE is full-iso, long-iso, iso, locale, relative or +FORMAT. FORMAT is interpreted like in date; with FORMAT1<newline>FORMAT2, FORMAT1 applies to non-recent files and FORMAT2 to recent files. A posix- prefix only takes effect outside the POSIX locale. Also read from the TIME_STYLE environment variable",
+        value_name("TIME_STYLE")
+    )]
+    time_style: Option<TimeStyle>,
+    // done
+    #[arg(short = 't', help = "Sort by time")]
+    time_sort: bool,
+    // Done
+    #[arg(
+        short = 'T',
+        long = "tabsize",
+        help = "Assume tabs stop at each COLS instead of 8",
+        value_name("COLS")
+    )]
+    tab_size: Option<u32>,
+    /// Distance between tab stops from -T or TABSIZE, 0 to pad with spaces only
+    #[arg(skip)]
+    tab_width: usize,
+    // Done
+    #[arg(
+        long = "total-size",
+        help = "Show the size and blocks of directories as the total of everything in them, counted like du"
+    )]
+    total_size: bool,
+    /// The usage of the directories which were walked for --total-size
+    #[arg(skip)]
+    total_sizes: RefCell<HashMap<PathBuf, Usage>>,
+    // Done
+    #[arg(long = "tree", help = "List subdirectories recursively as a tree")]
+    tree: bool,
+    // Done
+    #[arg(
+        short = 'u',
+        help = "With -lt: sort by, and show, access time; with -l: show access time and sort by name; otherwise: sort by access time, newest first"
+    )]
+    sort_access_time: bool,
+    // Done
+    #[arg(short = 'U', help = "Do not sort; list entries in directory order")]
+    no_sort: bool,
+    // Done
+    #[arg(short = 'v', help = "Natural sort of (version) numbers within text")]
+    sort_version: bool,
+    // Done
+    #[arg(
+        short = 'w',
+        long = "width",
+        help = "Set output width to COLS, 0 means no limit",
+        value_name("COLS")
+    )]
+    output_width: Option<u32>,
+    /// The width which is filled, from -w, COLUMNS or the terminal. 0 means there is no limit
+    #[arg(skip)]
+    line_length: usize,
+    // Done
+    #[arg(short = 'x', help = "List entries by lines instead of columns")]
+    list_lines: bool,
+    // Done
+    #[arg(short = 'X', help = "Sort alphabetically by entry extension")]
+    sort_extension: bool,
+    // Done
+    #[arg(long = "zero", help = "End each output line with NUL, not newline")]
+    end_nul: bool,
+    // Done
+    #[arg(short = '1', help = "List one file per line")]
+    one_line: bool,
+    // Planned for later updates
+    //#[arg(long = "update", help = "Control which existing files are updated")]
+    //update: Option<Update>,
+    #[arg(long = "verbose", help = "explain whats being done")]
+    verbose: bool,
+    #[clap(long, action = clap::ArgAction::HelpLong)]
+    help: Option<bool>,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+enum When {
+    #[default]
+    #[value(alias = "yes", alias = "force")]
+    Always,
+    #[value(alias = "tty", alias = "if-tty")]
+    Auto,
+    #[value(alias = "no", alias = "none")]
+    Never,
+}
+
+/// Which symbolic links are followed, the same modes as GNU
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Default)]
+enum Dereference {
+    #[default]
+    Never,
+    /// Command line arguments are followed if they point to a directory, the default unless
+    /// listing long, classifying or listing directories themselves
+    CommandLineSymlinkToDir,
+    CommandLine,
+    Always,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum FormatWord {
+    Across,
+    Commas,
+    Horizontal,
+    Long,
+    SingleColumn,
+    Verbose,
+    Vertical,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum QuotingWord {
+    Literal,
+    Locale,
+    Shell,
+    ShellAlways,
+    ShellEscape,
+    ShellEscapeAlways,
+    C,
+    CMaybe,
+    Escape,
+    Clocale,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum IndicatorWord {
+    None,
+    Slash,
+    FileType,
+    Classify,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum SortWord {
+    Name,
+    None,
+    Size,
+    Time,
+    Version,
+    Extension,
+    Width,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum TimeWord {
+    #[value(name = "atime", aliases(["access", "use"]))]
+    AccessTime,
+    #[value(name = "ctime", aliases(["status"]))]
+    MetadataChangeTime,
+    #[value(name = "mtime", aliases(["modification"]))]
+    ModifiedTime,
+    #[value(name = "birth", aliases(["creation"]))]
+    BirthTime,
+}
+
+#[derive(Debug, Clone)]
+struct EntryItem {
+    mode: String,
+    nlink: String,
+    /// None when the column isn't shown
+    owner: Option<Id>,
+    group: Option<Id>,
+    size: String,
+    /// Major and minor number of device files, which are shown instead of the size
+    device: Option<(String, String)>,
+    blocks: String,
+    timestamps: String,
+    processed_entry: Vec<u8>,
+    /// Where the name is in `processed_entry`, see `DisplayEntry`
+    name_range: (usize, usize),
+    inode: String,
+    author: Option<Id>,
+    /// The coloured git status, None outside of repositories
+    git: Option<String>,
+}
+
+/// A user or group in the long listing
+#[derive(Debug, Clone)]
+struct Id {
+    text: String,
+    /// Names are aligned to the left and numbers to the right, just like GNU
+    numeric: bool,
+}
+
+impl Id {
+    fn name(name: String) -> Id {
+        Id {
+            text: name,
+            numeric: false,
+        }
+    }
+
+    fn number(id: u32) -> Id {
+        Id {
+            text: id.to_string(),
+            numeric: true,
+        }
+    }
+
+    fn pad(&self, width: usize) -> String {
+        let padding = " ".repeat(width.saturating_sub(self.text.width()));
+        match self.numeric {
+            true => padding + &self.text,
+            false => self.text.clone() + &padding,
+        }
+    }
+}
+
+/// An entry ready to be printed by one of the list functions
+#[derive(Debug, Clone)]
+struct DisplayEntry {
+    /// The name with escapes, colours and indicator applied. Bytes, since names which aren't
+    /// UTF-8 are printed as they are
+    text: Vec<u8>,
+    /// How many columns `text` takes up in the terminal, escape codes not included
+    width: usize,
+    /// Where the name starts and ends in `text`, in bytes without escape codes. Used by --dired
+    name_range: (usize, usize),
+    /// Length of the inode and block count columns at the start of `text`, the tree branches go
+    /// right after them
+    columns: usize,
+    path: PathBuf,
+    /// What is shown for the entry, None if it couldn't be read
+    metadata: Option<Metadata>,
+}
+
+#[derive(Debug, Clone)]
+struct Longest {
+    longest_nlink: usize,
+    longest_owner: usize,
+    longest_group: usize,
+    longest_size: usize,
+    longest_inode: usize,
+    longest_blocks: usize,
+    longest_author: usize,
+    longest_time: usize,
+}
+
+pub fn main() {
+    // Operands don't have to be UTF-8, so the arguments are collected as they are
+    let mut arguments: Vec<OsString> = args_os().collect();
+    // skip first arg if it happens to be "blutils"
+    if Path::new(&arguments[0]).file_name() == Some(OsStr::new("blutils")) {
+        arguments.remove(0);
+    }
+    // We need the raw matches as well, the position of the format options decides which one wins
+    let matches = Cli::command().get_matches_from(arguments);
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    // Like GNU, every -I and --hide pattern counts, but the --hide ones are dropped by -a and -A
+    if !(cli.all || cli.almost_all) {
+        cli.ignore_pattern.extend(cli.hide.clone());
+    }
+    if cli.no_group_list {
+        cli.no_group = true;
+    }
+
+    // Like GNU, columns are only the default on a terminal, anything else gets one entry per line
+    let format = given_format(&matches, cli.format).unwrap_or(if stdout().is_terminal() {
+        FormatWord::Vertical
+    } else {
+        FormatWord::SingleColumn
+    });
+    cli.format = Some(format);
+    cli.list = matches!(format, FormatWord::Long | FormatWord::Verbose);
+
+    cli.indicator_style = Some(
+        given_indicator_style(&matches, &cli, stdout().is_terminal())
+            .unwrap_or(IndicatorWord::None),
+    );
+
+    cli.dereference_mode = last_given(
+        &matches,
+        &[
+            ("dereference", Some(Dereference::Always)),
+            ("dereference_argument", Some(Dereference::CommandLine)),
+            (
+                "dereference_argument_dir",
+                Some(Dereference::CommandLineSymlinkToDir),
+            ),
+        ],
+    )
+    .unwrap_or(
+        if cli.directory || cli.list || cli.indicator_style == Some(IndicatorWord::Classify) {
+            Dereference::Never
+        } else {
+            Dereference::CommandLineSymlinkToDir
+        },
+    );
+
+    if cli.full_time && cli.time_style.is_none() {
+        cli.time_style = Some(TimeStyle::from_str("full-iso").unwrap());
+    }
+    if cli.time_style.is_none() {
+        if let Ok(style) = var("TIME_STYLE") {
+            cli.time_style = match TimeStyle::from_str(&style) {
+                Ok(style) => Some(style),
+                Err(e) => {
+                    eprintln!("{}: {}", PROGRAM, e);
+                    exit(2)
+                }
+            };
+        }
+    }
+
+    // Just like GNU, file sizes are only scaled by the environment variables specific to ls, and
+    // -k only applies if nothing else was given
+    match last_given(
+        &matches,
+        &[
+            ("human_readable", Some(BlockSize::human(true))),
+            ("human_readable_1000", Some(BlockSize::human(false))),
+            ("block_size", cli.block_size),
+        ],
+    ) {
+        Some(block_size) => {
+            cli.file_block_size = block_size;
+            cli.output_block_size = block_size;
+        }
+        None => {
+            let from_env = ["LS_BLOCK_SIZE", "BLOCK_SIZE", "BLOCKSIZE"]
+                .iter()
+                .find_map(|name| Some((*name, var(name).ok()?)));
+            cli.output_block_size = from_env
+                .as_ref()
+                .and_then(|(_, spec)| BlockSize::from_str(spec).ok())
+                .unwrap_or_default();
+            cli.file_block_size = match from_env {
+                Some(("LS_BLOCK_SIZE" | "BLOCK_SIZE", _)) => cli.output_block_size,
+                _ => BlockSize::new(1),
+            };
+            if cli.kibibytes {
+                cli.output_block_size = BlockSize::new(1024);
+            }
+        }
+    }
+
+    // Quoting works the same way as in GNU, the last option wins, then QUOTING_STYLE and finally
+    // copy-pasteable names on a terminal and the names as they are otherwise
+    cli.quoting_style = last_given(
+        &matches,
+        &[
+            ("literal", Some(QuotingWord::Literal)),
+            ("print_escapes", Some(QuotingWord::Escape)),
+            ("quote_name", Some(QuotingWord::C)),
+            ("quoting_style", cli.quoting_style),
+        ],
+    )
+    .or_else(|| {
+        let style = var("QUOTING_STYLE").ok()?;
+        let parsed = QuotingWord::from_str(&style, false).ok();
+        if parsed.is_none() {
+            eprintln!(
+                "{}: ignoring invalid value of environment variable QUOTING_STYLE: '{}'",
+                PROGRAM, style
+            );
+        }
+        parsed
+    })
+    .or(Some(if stdout().is_terminal() {
+        QuotingWord::ShellEscape
+    } else {
+        QuotingWord::Literal
+    }));
+    cli.hide_control_chars = last_given(
+        &matches,
+        &[
+            ("hide_control_chars", Some(true)),
+            ("show_control_chars", Some(false)),
+        ],
+    )
+    .unwrap_or(stdout().is_terminal());
+
+    cli.time_display_sort = last_given(
+        &matches,
+        &[
+            ("sort_access_time", Some(TimeWord::AccessTime)),
+            ("sort_access_ctime", Some(TimeWord::MetadataChangeTime)),
+            ("time_display_sort", cli.time_display_sort),
+        ],
+    );
+    let sort_word = last_given(
+        &matches,
+        &[
+            ("size_sort", Some(SortWord::Size)),
+            ("time_sort", Some(SortWord::Time)),
+            ("no_sort", Some(SortWord::None)),
+            ("sort_version", Some(SortWord::Version)),
+            ("sort_extension", Some(SortWord::Extension)),
+            ("sort_word", cli.sort_word),
+        ],
+    );
+    // Without -l, -c and -u sort by their timestamp unless something else was asked for
+    cli.sort_word = match (sort_word, cli.time_display_sort) {
+        (None, Some(TimeWord::AccessTime | TimeWord::MetadataChangeTime | TimeWord::BirthTime))
+            if !cli.list =>
+        {
+            Some(SortWord::Time)
+        }
+        _ => sort_word,
+    };
+
+    if cli.no_sort_color {
+        cli.all = true;
+        cli.sort_word = Some(SortWord::None);
+        if cli.list {
+            cli.list = false;
+            cli.format = Some(FormatWord::Vertical);
+        }
+        cli.size_blocks = false;
+        cli.color = Some(When::Never);
+    }
+
+    cli.colors = match cli.color.unwrap_or(When::Auto) {
+        When::Always => LsColors::from_env(),
+        When::Auto if stdout().is_terminal() => LsColors::from_env(),
+        _ => None,
+    };
+    cli.hyperlink_host = match cli.hyperlink_when.unwrap_or(When::Never) {
+        When::Always => Some(hyperlink::host_name()),
+        When::Auto if stdout().is_terminal() => Some(hyperlink::host_name()),
+        _ => None,
+    };
+    cli.icons = match cli.icons_when.unwrap_or(When::Never) {
+        When::Always => Some(Icons::load()),
+        When::Auto if stdout().is_terminal() => Some(Icons::load()),
+        _ => None,
+    };
+
+    if cli.dired && cli.end_nul {
+        eprintln!("{}: --dired and --zero are incompatible", PROGRAM);
+        exit(2);
+    }
+
+    // Only the formats which fill lines need to know how wide they are
+    if matches!(
+        cli.format,
+        Some(
+            FormatWord::Vertical | FormatWord::Across | FormatWord::Horizontal | FormatWord::Commas
+        )
+    ) {
+        cli.line_length = line_length(&cli);
+        cli.tab_width = tab_width(&cli);
+    }
+
+    if let Some(lines) = last_given(&matches, &[("json", Some(false)), ("ndjson", Some(true))]) {
+        cli.json_output = Some(JsonOutput::new(lines));
+    }
+    if cli.dired && cli.list && cli.hyperlink_host.is_none() && cli.json_output.is_none() {
+        cli.dired_output = Some(Dired::default());
+    }
+    if (cli.git && cli.list) || cli.gitignore {
+        cli.git_repositories = Some(Repositories::default());
+    }
+
+    if let Some(json) = &cli.json_output {
+        json.begin();
+    }
+    ls(&cli);
+
+    if let Some(json) = &cli.json_output {
+        json.end();
+    }
+    if let Some(dired) = &cli.dired_output {
+        let style = cli.quoting_style.unwrap_or(QuotingWord::Literal);
+        dired.print_trailer(style.to_possible_value().unwrap().get_name());
+    }
+    exit(cli.exit_status.get());
+}
+
+/// The format chosen on the command line, None if there is none. Like GNU, -1 only changes a
+/// format which isn't long, so `ls -l -1` still gives a long listing
+fn given_format(matches: &ArgMatches, format: Option<FormatWord>) -> Option<FormatWord> {
+    let formats = [
+        ("list", Some(FormatWord::Long)),
+        ("list_no_owner", Some(FormatWord::Long)),
+        ("no_group_list", Some(FormatWord::Long)),
+        ("numeric_list", Some(FormatWord::Long)),
+        ("column", Some(FormatWord::Vertical)),
+        ("list_lines", Some(FormatWord::Across)),
+        ("fill_comma", Some(FormatWord::Commas)),
+        ("full_time", Some(FormatWord::Long)),
+        ("format", format),
+    ];
+    let format = last_given(matches, &formats);
+    match format {
+        Some(FormatWord::Long | FormatWord::Verbose) => format,
+        _ => last_given(
+            matches,
+            &[
+                &formats[..],
+                &[("one_line", Some(FormatWord::SingleColumn))],
+            ]
+            .concat(),
+        ),
+    }
+}
+
+/// The indicator style chosen on the command line, None if there is none. Like GNU,
+/// --classify=never and --classify=auto without a terminal don't switch off a style which was
+/// given before them, so only the last -F which turns classifying on counts
+fn given_indicator_style(matches: &ArgMatches, cli: &Cli, terminal: bool) -> Option<IndicatorWord> {
+    let classify = matches
+        .indices_of("classify")
+        .into_iter()
+        .flatten()
+        .zip(&cli.classify)
+        .filter(|(_, when)| match when {
+            When::Always => true,
+            When::Auto => terminal,
+            When::Never => false,
+        })
+        .map(|(index, _)| (index, IndicatorWord::Classify))
+        .last();
+    let style = last_given_at(
+        matches,
+        &[
+            ("file_type", Some(IndicatorWord::FileType)),
+            ("slash", Some(IndicatorWord::Slash)),
+            ("indicator_style", cli.indicator_style),
+        ],
+    );
+    classify
+        .into_iter()
+        .chain(style)
+        .max_by_key(|(index, _)| *index)
+        .map(|(_, style)| style)
+}
+
+/// Returns the value of whichever option was given last on the command line. Just like GNU the
+/// last option wins when they contradict each other, so `ls -C -m` separates the entries with
+/// commas
+fn last_given<T: Copy>(matches: &ArgMatches, options: &[(&str, Option<T>)]) -> Option<T> {
+    last_given_at(matches, options).map(|(_, value)| value)
+}
+
+/// Like `last_given`, along with the position of the option on the command line
+fn last_given_at<T: Copy>(
+    matches: &ArgMatches,
+    options: &[(&str, Option<T>)],
+) -> Option<(usize, T)> {
+    options
+        .iter()
+        .filter(|(id, _)| matches.value_source(id) == Some(ValueSource::CommandLine))
+        .filter_map(|(id, value)| Some((matches.indices_of(id)?.max()?, (*value)?)))
+        .max_by_key(|(index, _)| *index)
+}
+
+fn ls(cli: &Cli) {
+    // Like GNU, the operands which can't be accessed are reported first, then the files are
+    // listed as one group and the directories follow in the same order, each under a header
+    let mut operands = vec![];
+    for file in &cli.files {
+        match entry_metadata(cli, file, 0) {
+            Ok(_) => operands.push((file.as_os_str().to_owned(), file.to_owned(), 0)),
+            Err(e) => cannot_access(cli, file, &e, true),
+        }
+    }
+    let (directories, files): (Vec<_>, Vec<_>) =
+        sort_entries(cli, operands).into_iter().partition(|entry| {
+            !cli.directory && entry_metadata(cli, &entry.1, 0).is_ok_and(|m| m.is_dir())
+        });
+
+    // A lone directory is listed without a header
+    let headers = cli.recursive || !files.is_empty() || cli.files.len() > 1;
+    if !files.is_empty() {
+        print_entries(cli, files, false);
+        cli.printed_section.set(true);
+    }
+    for directory in directories {
+        if cli.tree {
+            tree_list(cli, &directory.1);
+        } else if cli.recursive {
+            recursive_list(cli, &directory.1, &mut vec![]);
+        } else {
+            list_directory(cli, &directory.1, 0, headers);
+        }
+    }
+}
+
+/// Reads the direct children of a directory, including "." and ".." if -a was supplied. `depth`
+/// is the depth of the children, counted from the command line operand
+fn read_directory(
+    cli: &Cli,
+    p: &Path,
+    depth: usize,
+) -> std::io::Result<Vec<(OsString, PathBuf, usize)>> {
+    // read_dir leaves out . and .., reading the directory directly keeps them where the
+    // directory returns them, which is the order GNU shows without sorting
+    let mut directory = Dir::open(
+        p,
+        OFlag::O_RDONLY | OFlag::O_DIRECTORY | OFlag::O_CLOEXEC,
+        Mode::empty(),
+    )?;
+    let entries = directory
+        .iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = OsStr::from_bytes(e.file_name().to_bytes());
+            if !cli.all && (name == "." || name == "..") {
+                return None;
+            }
+            Some((name.to_owned(), p.join(name), depth))
+        })
+        .collect();
+    Ok(entries)
+}
+
+/// Lists a single directory, under a "dir:" header if `header` is set, and returns the
+/// subdirectories in the order they were printed, so that the recursive listing can descend into
+/// them in the same order as GNU does. Directories which can't be opened are reported without a
+/// header, which is a serious error for the ones given on the command line, at depth 0
+fn list_directory(cli: &Cli, p: &Path, depth: usize, header: bool) -> Vec<PathBuf> {
+    let entries = match read_directory(cli, p, depth + 1) {
+        Ok(entries) => entries,
+        Err(e) => {
+            report(
+                cli,
+                depth == 0,
+                &format!(
+                    "cannot open directory {}: {}",
+                    quote_path(p),
+                    error_text(&e)
+                ),
+            );
+            return vec![];
+        }
+    };
+    if header {
+        print_header(cli, p);
+    }
+    let entries = sort_entries(cli, filter_entries(cli, entries));
+
+    let subdirectories = entries
+        .iter()
+        .filter(|entry| entry.0 != "." && entry.0 != "..")
+        // Links to directories are only followed with -L, the recursive listing catches the loops
+        .filter(|entry| entry_metadata(cli, &entry.1, entry.2).is_ok_and(|m| m.is_dir()))
+        .map(|entry| entry.1.clone())
+        .collect();
+
+    print_entries(cli, entries, true);
+    subdirectories
+}
+
+/// Prints the "dir:" header of a directory listing, with a blank line in front of it unless it
+/// is the first thing printed
+fn print_header(cli: &Cli, p: &Path) {
+    if cli.json_output.is_some() {
+        return;
+    }
+    let header = link_name(cli, p, &quote_name(cli, p.as_os_str(), b":").0, false);
+    let separate = cli.printed_section.replace(true);
+    match &cli.dired_output {
+        Some(dired) => {
+            if separate {
+                dired.print("\n");
+            }
+            dired.print("  ");
+            dired.print_subdirectory(&header);
+            dired.print(":\n");
+        }
+        // GNU always ends the header lines with a newline, even with --zero
+        None => {
+            if separate {
+                println!();
+            }
+            print_bytes(&header);
+            println!(":");
+        }
+    }
+}
+
+/// Lists the directory under a header, then does the same for every subdirectory. `ancestors`
+/// holds the device and inode of the directories being listed, so links back up to them aren't
+/// followed forever
+fn recursive_list(cli: &Cli, p: &Path, ancestors: &mut Vec<(u64, u64)>) {
+    let id = p
+        .metadata()
+        .map(|metadata| (metadata.dev(), metadata.ino()))
+        .unwrap_or_default();
+    if ancestors.contains(&id) {
+        already_listed(cli, p);
+        return;
+    }
+
+    let subdirectories = list_directory(cli, p, ancestors.len(), true);
+    ancestors.push(id);
+    if cli.level.is_some_and(|level| ancestors.len() >= level) {
+        ancestors.pop();
+        return;
+    }
+    for subdirectory in subdirectories {
+        recursive_list(cli, &subdirectory, ancestors);
+    }
+    ancestors.pop();
+}
+
+/// Lists the directory and everything below it as a tree, with the directory itself at the root.
+/// Every entry is put behind the branches which connect it to its parent
+fn tree_list(cli: &Cli, p: &Path) {
+    let mut entries = vec![(p.as_os_str().to_owned(), p.to_path_buf(), 0)];
+    let mut branches = vec![String::new()];
+    let mut ancestors = vec![];
+    if !tree_entries(cli, p, "", &mut ancestors, &mut entries, &mut branches) {
+        return;
+    }
+
+    let metadata = read_metadata(cli, &entries);
+    if let Some(json) = &cli.json_output {
+        print_json(cli, json, &entries, &metadata);
+        return;
+    }
+    if cli.printed_section.replace(true) {
+        println!();
+    }
+    let mut entries = treat_entries(cli, entries, metadata);
+    for (entry, branch) in entries.iter_mut().zip(branches) {
+        entry
+            .text
+            .splice(entry.columns..entry.columns, branch.bytes());
+        entry.width += branch.width();
+        entry.name_range = (
+            entry.name_range.0 + branch.len(),
+            entry.name_range.1 + branch.len(),
+        );
+    }
+    if cli.list {
+        list_list(cli, entries);
+    } else {
+        for entry in entries {
+            print_bytes(&entry.text);
+            print!("{}", if cli.end_nul { "\0" } else { "\n" });
+        }
+    }
+}
+
+/// Adds the entries of the directory and of its subdirectories, with the branches in front of
+/// them. `indent` continues the branches of the parents. Returns whether the directory could be
+/// read
+fn tree_entries(
+    cli: &Cli,
+    p: &Path,
+    indent: &str,
+    ancestors: &mut Vec<(u64, u64)>,
+    entries: &mut Vec<(OsString, PathBuf, usize)>,
+    branches: &mut Vec<String>,
+) -> bool {
+    let children = match read_directory(cli, p, ancestors.len() + 1) {
+        Ok(children) => children,
+        Err(e) => {
+            report(
+                cli,
+                ancestors.is_empty(),
+                &format!(
+                    "cannot open directory {}: {}",
+                    quote_path(p),
+                    error_text(&e)
+                ),
+            );
+            return false;
+        }
+    };
+    let children: Vec<_> = sort_entries(cli, filter_entries(cli, children))
+        .into_iter()
+        .filter(|entry| entry.0 != "." && entry.0 != "..")
+        .collect();
+
+    // Links to directories are only followed with -L, which could lead back up the tree
+    let id = p
+        .metadata()
+        .map(|metadata| (metadata.dev(), metadata.ino()))
+        .unwrap_or_default();
+    ancestors.push(id);
+    let depth = ancestors.len();
+    let count = children.len();
+    for (i, child) in children.into_iter().enumerate() {
+        let last = i + 1 == count;
+        let path = child.1.clone();
+        let is_dir = entry_metadata(cli, &path, depth).is_ok_and(|m| m.is_dir());
+        entries.push(child);
+        branches.push(format!("{}{}", indent, if last { "└── " } else { "├── " }));
+
+        if !is_dir || cli.level.is_some_and(|level| depth >= level) {
+            continue;
+        }
+        let looped = path
+            .metadata()
+            .is_ok_and(|metadata| ancestors.contains(&(metadata.dev(), metadata.ino())));
+        if looped {
+            already_listed(cli, &path);
+            continue;
+        }
+        let indent = format!("{}{}", indent, if last { "    " } else { "│   " });
+        tree_entries(cli, &path, &indent, ancestors, entries, branches);
+    }
+    ancestors.pop();
+    true
+}
+
+/// Prints the entries in the chosen format. Directory listings start with the total amount of
+/// blocks used when listing long or with -s
+fn print_entries(cli: &Cli, entries: Vec<(OsString, PathBuf, usize)>, directory: bool) {
+    let metadata = read_metadata(cli, &entries);
+    if let Some(json) = &cli.json_output {
+        print_json(cli, json, &entries, &metadata);
+        return;
+    }
+
+    if directory && (cli.list || cli.size_blocks) {
+        let total = entries
+            .iter()
+            .zip(&metadata)
+            .filter_map(|(entry, metadata)| Some(usage(cli, &entry.1, metadata.as_ref()?).blocks))
+            .sum();
+        let line = format!(
+            "total {}{}",
+            cli.output_block_size.format(total, 512),
+            if cli.end_nul { "\0" } else { "\n" }
+        );
+        match &cli.dired_output {
+            Some(dired) => dired.print(format!("  {}", line)),
+            None => print!("{}", line),
+        }
+    }
+
+    let entries = treat_entries(cli, entries, metadata);
+
+    // Finally trigger the right function
+    if !cli.list {
+        normal_list(cli, entries);
+    } else {
+        list_list(cli, entries)
+    }
+}
+
+/// Prints every entry as a JSON object. Entries which couldn't be read only have their name, path
+/// and depth, everything else is null
+fn print_json(
+    cli: &Cli,
+    json: &JsonOutput,
+    entries: &[(OsString, PathBuf, usize)],
+    metadata: &[Option<Metadata>],
+) {
+    for (entry, metadata) in entries.iter().zip(metadata) {
+        let timestamps = metadata.as_ref().map(FileTimestamps::new);
+        let usage = metadata.as_ref().map(|m| usage(cli, &entry.1, m));
+        let time = |word| {
+            let timestamp = timestamps?.get(word)?;
+            Some(
+                timestamp
+                    .datetime
+                    .to_rfc3339_opts(SecondsFormat::AutoSi, false),
+            )
+        };
+        let target = read_link(&entry.1).ok();
+        let object = Object::default()
+            .string("name", Some(&entry.0.to_string_lossy()))
+            .string("path", Some(&entry.1.to_string_lossy()))
+            .string("type", metadata.as_ref().map(file_type_name))
+            .string(
+                "mode",
+                metadata
+                    .as_ref()
+                    .map(|m| format!("{:04o}", m.mode() & 0o7777))
+                    .as_deref(),
+            )
+            .string(
+                "permissions",
+                metadata
+                    .as_ref()
+                    .map(|m| m.permissions().mode_struct().to_string())
+                    .as_deref(),
+            )
+            .number("nlink", metadata.as_ref().map(|m| m.nlink()))
+            .number("uid", metadata.as_ref().map(|m| m.uid()))
+            .string("user", metadata.as_ref().and_then(|m| m.owner()).as_deref())
+            .number("gid", metadata.as_ref().map(|m| m.gid()))
+            .string(
+                "group",
+                metadata.as_ref().and_then(|m| m.group()).as_deref(),
+            )
+            .number("size", usage.map(|usage| usage.apparent))
+            .number("blocks", usage.map(|usage| usage.blocks))
+            .number("inode", metadata.as_ref().map(|m| m.ino()))
+            .string("accessed", time(TimeWord::AccessTime).as_deref())
+            .string("modified", time(TimeWord::ModifiedTime).as_deref())
+            .string("changed", time(TimeWord::MetadataChangeTime).as_deref())
+            .string("created", time(TimeWord::BirthTime).as_deref())
+            .string(
+                "target",
+                target.as_ref().map(|t| t.to_string_lossy()).as_deref(),
+            )
+            .number("depth", Some(entry.2));
+        json.print(&object);
+    }
+}
+
+fn file_type_name(metadata: &Metadata) -> &'static str {
+    let file_type = metadata.file_type();
+    if file_type.is_dir() {
+        "directory"
+    } else if file_type.is_symlink() {
+        "symlink"
+    } else if file_type.is_file() {
+        "file"
+    } else if file_type.is_fifo() {
+        "fifo"
+    } else if file_type.is_socket() {
+        "socket"
+    } else if file_type.is_block_device() {
+        "block_device"
+    } else if file_type.is_char_device() {
+        "char_device"
+    } else {
+        "unknown"
+    }
+}
+
+/// Reads the metadata of every entry. Entries which can't be read are still listed. GNU only
+/// reads them when the output needs it, so they are only reported then
+fn read_metadata(cli: &Cli, entries: &[(OsString, PathBuf, usize)]) -> Vec<Option<Metadata>> {
+    let report = cli.list
+        || cli.inode
+        || cli.size_blocks
+        || cli.recursive
+        || cli.tree
+        || cli.json_output.is_some()
+        || cli.group_directories_first
+        || cli.colors.is_some()
+        || cli.indicator_style != Some(IndicatorWord::None)
+        || cli.hyperlink_host.is_some()
+        || matches!(cli.sort_word, Some(SortWord::Size | SortWord::Time));
+    entries
+        .iter()
+        .map(|entry| match entry_metadata(cli, &entry.1, entry.2) {
+            Ok(metadata) => Some(metadata),
+            Err(e) => {
+                if report {
+                    cannot_access(cli, &entry.1, &e, entry.2 == 0);
+                }
+                None
+            }
+        })
+        .collect()
+}
+
+/// What the entry takes up. With --total-size directories count everything below them like du.
+/// The totals of the directories below are kept from the same walk, so -R and trees walk every
+/// directory only once
+fn usage(cli: &Cli, p: &Path, metadata: &Metadata) -> Usage {
+    if !cli.total_size || !metadata.is_dir() {
+        return Usage::from_metadata(metadata);
+    }
+    if let Some(usage) = cli.total_sizes.borrow().get(p) {
+        return *usage;
+    }
+    let mut disk_usage = DiskUsage::new(cli.dereference_mode == Dereference::Always);
+    let mut visit = |entry: &Path, metadata: &Metadata, usage: Usage| {
+        if metadata.is_dir() {
+            cli.total_sizes
+                .borrow_mut()
+                .insert(entry.to_path_buf(), usage);
+        }
+    };
+    let usage = disk_usage
+        .walk(p, &mut visit, &mut |action, entry, e| {
+            report(
+                cli,
+                false,
+                &format!("{} {}: {}", action, quote_path(entry), error_text(e)),
+            )
+        })
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| Usage::from_metadata(metadata));
+    cli.total_sizes.borrow_mut().insert(p.to_path_buf(), usage);
+    usage
+}
+
+/// The metadata which is shown for an entry, which is the link itself unless the dereference
+/// mode says to follow it. Entries at depth 0 were given on the command line
+fn entry_metadata(cli: &Cli, p: &Path, depth: usize) -> std::io::Result<Metadata> {
+    match cli.dereference_mode {
+        Dereference::Always => p.metadata(),
+        Dereference::CommandLine if depth == 0 => p.metadata(),
+        // Anything but a directory, or a link which is broken or loops, is shown as the link
+        Dereference::CommandLineSymlinkToDir if depth == 0 => match p.metadata() {
+            Ok(metadata) if metadata.is_dir() => Ok(metadata),
+            Err(e) if e.kind() != ErrorKind::NotFound && e.raw_os_error() != Some(ELOOP) => Err(e),
+            _ => p.symlink_metadata(),
+        },
+        _ => p.symlink_metadata(),
+    }
+}
+
+/// Reports a file which couldn't be read, like "ls: cannot access 'x': No such file or directory".
+/// Only operands given on the command line are serious errors
+fn cannot_access(cli: &Cli, p: &Path, e: &std::io::Error, command_line: bool) {
+    // GNU leaves out the "./" in front of entries of the current directory
+    let p = match p.parent() {
+        Some(parent) if parent == Path::new(".") && !command_line => {
+            p.strip_prefix(".").unwrap_or(p)
+        }
+        _ => p,
+    };
+    report(
+        cli,
+        command_line,
+        &format!("cannot access {}: {}", quote_path(p), error_text(e)),
+    );
+}
+
+/// Reports a directory which links back to one of the directories it is in
+fn already_listed(cli: &Cli, p: &Path) {
+    report(
+        cli,
+        true,
+        &format!(
+            "{}: not listing already-listed directory",
+            String::from_utf8_lossy(&quote(
+                p.as_os_str().as_bytes(),
+                QuotingWord::ShellEscape,
+                b":"
+            ))
+        ),
+    );
+}
+
+/// Prints the error message and raises the exit status, to 2 for serious errors and to 1 for
+/// everything else
+fn report(cli: &Cli, serious: bool, message: &str) {
+    eprintln!("{}: {}", PROGRAM, message);
+    let status = if serious { 2 } else { 1 };
+    cli.exit_status.set(cli.exit_status.get().max(status));
+}
+
+/// The path quoted like in GNU's error messages
+fn quote_path(p: &Path) -> String {
+    quote_arg(p.as_os_str().as_bytes())
+}
+
+/// Quotes text for an error message, like 'text'
+fn quote_arg(text: &[u8]) -> String {
+    String::from_utf8_lossy(&quote(text, QuotingWord::ShellEscapeAlways, b"")).to_string()
+}
+
+/// The message of the error, without the error number the standard library adds
+fn error_text(e: &std::io::Error) -> String {
+    let message = e.to_string();
+    message
+        .split(" (os error")
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Removes the entries which should not be listed
+fn filter_entries(
+    cli: &Cli,
+    mut entries: Vec<(OsString, PathBuf, usize)>,
+) -> Vec<(OsString, PathBuf, usize)> {
+    if let Some(suffix) = &cli.ignore_backups {
+        entries.retain(|x| !x.0.as_bytes().ends_with(suffix.as_bytes()));
+    }
+
+    for pattern in &cli.ignore_pattern {
+        let re = match Regex::new(pattern) {
+            Ok(x) => x,
+            Err(e) => {
+                eprintln!("Supplied PATTERN was not a valid regex pattern: {}", e);
+                exit(1);
+            }
+        };
+
+        entries.retain(|x| !re.is_match(x.0.as_bytes()));
+    }
+
+    // If the all and almost all mode isn't activated we need to do some filtering
+    if !cli.almost_all && !cli.all {
+        entries.retain(|f| !f.0.as_bytes().starts_with(b"."));
+    }
+
+    if let Some(repositories) = cli.git_repositories.as_ref().filter(|_| cli.gitignore) {
+        entries.retain(|f| f.0 == "." || f.0 == ".." || !repositories.is_ignored(&f.1));
+    }
+    entries
+}
+
+/// Sorts the entries by the chosen sort word, the name breaks any ties. Just like GNU -r reverses
+/// the whole order but directories still come first with --group-directories-first
+fn sort_entries(
+    cli: &Cli,
+    entries: Vec<(OsString, PathBuf, usize)>,
+) -> Vec<(OsString, PathBuf, usize)> {
+    let sort_word = cli.sort_word.unwrap_or(SortWord::Name);
+    // Directory order, which also means no reversing or grouping
+    if sort_word == SortWord::None {
+        return entries;
+    }
+
+    let byte_order = c_locale("LC_COLLATE");
+    let time_word = cli.time_display_sort.unwrap_or(TimeWord::ModifiedTime);
+    // Look up the metadata once per entry instead of once per comparison
+    let mut entries: Vec<(SortKey, (OsString, PathBuf, usize))> = entries
+        .into_iter()
+        .map(|entry| {
+            let metadata = entry_metadata(cli, &entry.1, entry.2).ok();
+            let mut key = SortKey::new(metadata.as_ref(), time_word);
+            // Like GNU, links to directories are grouped with the directories
+            if metadata.as_ref().is_some_and(|m| m.is_symlink()) {
+                key.directory = entry.1.metadata().is_ok_and(|m| m.is_dir());
+            }
+            // Directories are only walked for --total-size when their size is needed
+            if let (SortWord::Size, Some(metadata)) = (sort_word, &metadata) {
+                key.size = usage(cli, &entry.1, metadata).apparent;
+            }
+            // Like GNU, the width is the one the name is shown with, after quoting
+            if sort_word == SortWord::Width {
+                key.width = quoting::width(&quote_name(cli, &entry.0, b"").0);
+            }
+            (key, entry)
+        })
+        .collect();
+
+    entries.sort_by(|(a_key, a), (b_key, b)| {
+        let ordering = match sort_word {
+            // Largest and newest first
+            SortWord::Size => b_key.size.cmp(&a_key.size),
+            SortWord::Time => b_key.time.cmp(&a_key.time),
+            SortWord::Version => filevercmp(a.0.as_bytes(), b.0.as_bytes()),
+            SortWord::Extension => compare_names(extension(&a.0), extension(&b.0), byte_order),
+            SortWord::Width => a_key.width.cmp(&b_key.width),
+            SortWord::Name | SortWord::None => Ordering::Equal,
+        }
+        .then_with(|| {
+            if sort_word == SortWord::Version {
+                a.0.cmp(&b.0)
+            } else {
+                compare_names(&a.0, &b.0, byte_order)
+            }
+        });
+
+        if cli.reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+
+    // This sort is stable, so the order within directories and files is kept
+    if cli.group_directories_first {
+        entries.sort_by_key(|(key, _)| !key.directory);
+    }
+    entries.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// Everything the entries can be sorted on
+struct SortKey {
+    size: u64,
+    time: (i64, i64),
+    directory: bool,
+    /// Columns the quoted name takes up, only worked out for --sort=width
+    width: usize,
+}
+
+impl SortKey {
+    fn new(metadata: Option<&Metadata>, time_word: TimeWord) -> SortKey {
+        let Some(metadata) = metadata else {
+            return SortKey {
+                size: 0,
+                time: (0, 0),
+                directory: false,
+                width: 0,
+            };
+        };
+        // Just like GNU, files without a birth time sort as if they were born just before 1970
+        let time = FileTimestamps::new(metadata)
+            .get(time_word)
+            .map(|timestamp| (timestamp.unix, timestamp.nanos))
+            .unwrap_or((-1, -1));
+        SortKey {
+            size: metadata.size(),
+            time,
+            directory: metadata.is_dir(),
+            width: 0,
+        }
+    }
+}
+
+/// Whether the locale category is the C locale, which is also what you get when no locale is set.
+/// GNU sorts by byte value and uses its own time formats there
+fn c_locale(category: &str) -> bool {
+    let locale = ["LC_ALL", category, "LANG"]
+        .iter()
+        .find_map(|name| var(name).ok().filter(|value| !value.is_empty()))
+        .unwrap_or_default();
+    locale.is_empty() || locale == "C" || locale == "POSIX" || locale.starts_with("C.")
+}
+
+/// Compares names by byte value in the C locale, otherwise case insensitively. Bytes which aren't
+/// UTF-8 are compared as replacement characters, the byte values break the tie
+fn compare_names(a: &OsStr, b: &OsStr, byte_order: bool) -> Ordering {
+    if byte_order {
+        a.as_bytes().cmp(b.as_bytes())
+    } else {
+        a.to_string_lossy()
+            .to_lowercase()
+            .cmp(&b.to_string_lossy().to_lowercase())
+            .then_with(|| a.as_bytes().cmp(b.as_bytes()))
+    }
+}
+
+/// Everything from the last dot, or nothing if there is no dot
+fn extension(name: &OsStr) -> &OsStr {
+    let name = name.as_bytes();
+    let start = name.iter().rposition(|c| *c == b'.').unwrap_or(name.len());
+    OsStr::from_bytes(&name[start..])
+}
+
+/// Turns the entries into the names which will be printed, escaped, coloured and classified
+fn treat_entries(
+    cli: &Cli,
+    entries_list: Vec<(OsString, PathBuf, usize)>,
+    metadata: Vec<Option<Metadata>>,
+) -> Vec<DisplayEntry> {
+    // Quoting goes before the colours so the escape codes don't get quoted
+    let (entries, quoted): (Vec<_>, Vec<bool>) = entries_list
+        .into_iter()
+        .map(|entry| {
+            let (name, quoted) = quote_name(cli, &entry.0, b"");
+            ((name, entry.1, entry.2), quoted)
+        })
+        .unzip();
+    // Names without quotes are moved one column to the right so they line up with the quoted
+    // names next to them, but only in the styles which leave out quotes when they aren't needed
+    let align_quotes = quoted.contains(&true)
+        && matches!(
+            cli.quoting_style,
+            Some(QuotingWord::Shell | QuotingWord::ShellEscape | QuotingWord::CMaybe)
+        )
+        && match cli.format {
+            Some(FormatWord::Long | FormatWord::Verbose) => true,
+            Some(FormatWord::Commas | FormatWord::SingleColumn) => false,
+            _ => cli.line_length != 0,
+        };
+
+    // Outside of long listings the inode and block count are put in front of the name, padded to
+    // the same width unless the entries are separated by commas
+    // Entries which couldn't be read get a question mark, just like GNU
+    let inodes: Vec<String> = metadata
+        .iter()
+        .map(|metadata| match (cli.inode && !cli.list, metadata) {
+            (true, Some(metadata)) => metadata.ino().to_string(),
+            (true, None) => String::from("?"),
+            (false, _) => String::new(),
+        })
+        .collect();
+    let block_counts: Vec<String> = entries
+        .iter()
+        .zip(&metadata)
+        .map(
+            |(entry, metadata)| match (cli.size_blocks && !cli.list, metadata) {
+                (true, Some(metadata)) => cli
+                    .output_block_size
+                    .format(usage(cli, &entry.1, metadata).blocks, 512),
+                (true, None) => String::from("?"),
+                (false, _) => String::new(),
+            },
+        )
+        .collect();
+    let (inode_width, blocks_width) = match cli.format {
+        Some(FormatWord::Commas) => (0, 0),
+        _ => (
+            inodes.iter().map(|i| i.len()).max().unwrap_or(0),
+            block_counts.iter().map(|b| b.len()).max().unwrap_or(0),
+        ),
+    };
+
+    entries
+        .into_iter()
+        .zip(quoted.into_iter().zip(metadata))
+        .zip(inodes.into_iter().zip(block_counts))
+        .map(|((entry, (quoted, metadata)), (inode, block_count))| {
+            let mut prefix = String::new();
+            if !inode.is_empty() {
+                prefix += &format!("{: >width$} ", inode, width = inode_width);
+            }
+            if !block_count.is_empty() {
+                prefix += &format!("{: >width$} ", block_count, width = blocks_width);
+            }
+            let columns = prefix.len();
+            if align_quotes && !quoted {
+                prefix.push(' ');
+            }
+            // The icon is coloured along with the name
+            let icon = match &cli.icons {
+                Some(icons) => {
+                    let name = entry.1.file_name().map(|name| name.to_string_lossy());
+                    match icons.get(&name.unwrap_or_default(), metadata.as_ref()) {
+                        "" => String::new(),
+                        icon => format!("{} ", icon),
+                    }
+                }
+                None => String::new(),
+            };
+            // Like GNU, entries which can't be followed still show the type of the link. Long
+            // listings show the indicator of the target after the arrow instead
+            let link = entry.1.symlink_metadata().ok();
+            let indicator = match metadata.as_ref().or(link.as_ref()) {
+                Some(metadata) if cli.list && metadata.file_type().is_symlink() => "",
+                metadata => indicator(cli, metadata),
+            };
+            // The width has to be calculated before colouring, the escape codes take up no
+            // space in the terminal
+            let width =
+                prefix.width() + icon.width() + quoting::width(&entry.0) + indicator.width();
+            let text = [
+                icon.as_bytes(),
+                &link_name(cli, &entry.1, &entry.0, align_quotes && quoted),
+            ]
+            .concat();
+            let name = match &cli.colors {
+                Some(colors) => {
+                    let target = entry.1.metadata().ok();
+                    colors.paint(
+                        &text,
+                        colors.sequence(
+                            &entry.1.to_string_lossy(),
+                            metadata.as_ref(),
+                            target.as_ref(),
+                        ),
+                    )
+                }
+                None => text,
+            };
+            let start = prefix.len() + icon.len();
+            DisplayEntry {
+                name_range: (start, start + entry.0.len()),
+                text: [prefix.as_bytes(), &name, indicator.as_bytes()].concat(),
+                width,
+                columns,
+                path: entry.1,
+                metadata,
+            }
+        })
+        .collect()
+}
+
+fn normal_list(cli: &Cli, entries: Vec<DisplayEntry>) {
+    let line_length = cli.line_length;
+    match cli.format.unwrap_or(FormatWord::Vertical) {
+        FormatWord::SingleColumn => {
+            for entry in entries {
+                print_bytes(&entry.text);
+                print!("{}", if cli.end_nul { "\0" } else { "\n" })
+            }
+        }
+        // Without a limit everything fits on one line, like GNU there is no layout to search for
+        _ if line_length == 0 && cli.format != Some(FormatWord::Commas) => {
+            print_with_separator(cli, &entries, ' ', 0)
+        }
+        FormatWord::Across | FormatWord::Horizontal => print_horizontal(cli, &entries, line_length),
+        FormatWord::Commas => print_with_separator(cli, &entries, ',', line_length),
+        _ => print_many_per_line(cli, &entries, line_length),
+    }
+}
+
+/// The width to fill. Taken from -w, then the COLUMNS environment variable, then the terminal and
+/// finally 80 columns. 0 means there is no limit
+fn line_length(cli: &Cli) -> usize {
+    if let Some(width) = cli.output_width {
+        return width as usize;
+    }
+    match var("COLUMNS") {
+        Ok(columns) if !columns.is_empty() => match columns.parse() {
+            Ok(width) => return width,
+            Err(_) => eprintln!(
+                "{}: ignoring invalid width in environment variable COLUMNS: {}",
+                PROGRAM,
+                quote_arg(columns.as_bytes())
+            ),
+        },
+        _ => {}
+    }
+    termsize::get()
+        .map(|size| size.cols as usize)
+        .filter(|cols| *cols > 0)
+        .unwrap_or(80)
+}
+
+/// Distance between tab stops, taken from -T, then the TABSIZE environment variable and finally
+/// 8. Like GNU, coloured output is only padded with spaces
+fn tab_width(cli: &Cli) -> usize {
+    if cli.colors.is_some() {
+        return 0;
+    }
+    if let Some(width) = cli.tab_size {
+        return width as usize;
+    }
+    match var("TABSIZE") {
+        Ok(size) => size.parse().unwrap_or_else(|_| {
+            eprintln!(
+                "{}: ignoring invalid tab size in environment variable TABSIZE: {}",
+                PROGRAM,
+                quote_arg(size.as_bytes())
+            );
+            8
+        }),
+        Err(_) => 8,
+    }
+}
+
+/// Narrowest a column can be, GNU uses the same. One character and two separating spaces
+const MIN_COLUMN_WIDTH: usize = 3;
+
+/// Finds the layout with the most columns which still fits within the line length and returns
+/// the width of every column. Works the same way as GNU, so every column is only as wide as its
+/// own longest entry
+fn calculate_columns(entries: &[DisplayEntry], line_length: usize, by_columns: bool) -> Vec<usize> {
+    let files = entries.len();
+    if files == 0 {
+        return vec![];
+    }
+
+    // One extra column for the first one which has no separator
+    let max_idx = line_length.div_ceil(MIN_COLUMN_WIDTH);
+    let max_cols = if 0 < max_idx && max_idx < files {
+        max_idx
+    } else {
+        files
+    };
+
+    // Layout number i has i + 1 columns
+    let mut layouts: Vec<(bool, usize, Vec<usize>)> = (0..max_cols)
+        .map(|i| {
+            (
+                true,
+                (i + 1) * MIN_COLUMN_WIDTH,
+                vec![MIN_COLUMN_WIDTH; i + 1],
+            )
+        })
+        .collect();
+
+    for (filesno, entry) in entries.iter().enumerate() {
+        for (i, (valid, line_len, columns)) in layouts.iter_mut().enumerate() {
+            if !*valid {
+                continue;
+            }
+            let idx = if by_columns {
+                filesno / files.div_ceil(i + 1)
+            } else {
+                filesno % (i + 1)
+            };
+            // Every column except the last one is followed by two spaces
+            let real_length = entry.width + if idx == i { 0 } else { 2 };
+            if columns[idx] < real_length {
+                *line_len += real_length - columns[idx];
+                columns[idx] = real_length;
+                *valid = line_length == 0 || *line_len < line_length;
+            }
+        }
+    }
+
+    let cols = (1..=max_cols)
+        .rev()
+        .find(|cols| layouts[cols - 1].0)
+        .unwrap_or(1);
+    layouts.swap_remove(cols - 1).2
+}
+
+/// Pads from column `from` to column `to`. Tabs are used wherever they get closer to `to` than a
+/// space, unless `tab_width` is 0
+fn indent(mut from: usize, to: usize, tab_width: usize) {
+    let mut padding = String::new();
+    while from < to {
+        if tab_width != 0 && to / tab_width > (from + 1) / tab_width {
+            padding.push('\t');
+            from += tab_width - from % tab_width;
+        } else {
+            padding.push(' ');
+            from += 1;
+        }
+    }
+    print!("{}", padding);
+}
+
+/// Lists the entries sorted vertically, in columns (-C)
+fn print_many_per_line(cli: &Cli, entries: &[DisplayEntry], line_length: usize) {
+    let columns = calculate_columns(entries, line_length, true);
+    if columns.is_empty() {
+        return;
+    }
+    let rows = entries.len().div_ceil(columns.len());
+
+    for row in 0..rows {
+        let mut pos = 0;
+        let mut filesno = row;
+        for column_width in &columns {
+            let entry = &entries[filesno];
+            print_bytes(&entry.text);
+            filesno += rows;
+            if filesno >= entries.len() {
+                break;
+            }
+            indent(pos + entry.width, pos + column_width, cli.tab_width);
+            pos += column_width;
+        }
+        print!("{}", if cli.end_nul { "\0" } else { "\n" })
+    }
+}
+
+/// Lists the entries after each other with a separator in between, filling each line as far as
+/// it goes (-m)
+fn print_with_separator(cli: &Cli, entries: &[DisplayEntry], separator: char, line_length: usize) {
+    if entries.is_empty() {
+        return;
+    }
+
+    let mut pos = 0;
+    for (filesno, entry) in entries.iter().enumerate() {
+        if filesno != 0 {
+            // The separator and a space fits on this line if the next entry does. A line length
+            // of 0 means there is no limit
+            if line_length == 0 || pos + entry.width + 2 < line_length {
+                pos += 2;
+                print!("{} ", separator);
+            } else {
+                pos = 0;
+                print!("{}{}", separator, if cli.end_nul { "\0" } else { "\n" });
+            }
+        }
+        print_bytes(&entry.text);
+        pos += entry.width;
+    }
+    print!("{}", if cli.end_nul { "\0" } else { "\n" })
+}
+
+/// Lists the entries sorted horizontally, in rows (-x)
+fn print_horizontal(cli: &Cli, entries: &[DisplayEntry], line_length: usize) {
+    let columns = calculate_columns(entries, line_length, false);
+    if columns.is_empty() {
+        return;
+    }
+
+    let mut pos = 0;
+    for (filesno, entry) in entries.iter().enumerate() {
+        let column = filesno % columns.len();
+        if filesno != 0 {
+            if column == 0 {
+                print!("{}", if cli.end_nul { "\0" } else { "\n" });
+                pos = 0;
+            } else {
+                let previous = &entries[filesno - 1];
+                indent(
+                    pos + previous.width,
+                    pos + columns[column - 1],
+                    cli.tab_width,
+                );
+                pos += columns[column - 1];
+            }
+        }
+        print_bytes(&entry.text);
+    }
+    print!("{}", if cli.end_nul { "\0" } else { "\n" })
+}
+
+fn list_list(cli: &Cli, display_entries: Vec<DisplayEntry>) {
+    let mut entries: Vec<EntryItem> = vec![];
+    let time_style = cli.time_style.clone().unwrap_or_default();
+    let now = Local::now();
+    let unknown_time_width = time_style
+        .format(&Local.timestamp_opt(0, 0).unwrap(), &now)
+        .width();
+    for entry in display_entries {
+        let git = cli
+            .git_repositories
+            .as_ref()
+            .filter(|_| cli.git)
+            .and_then(|repositories| {
+                let status = repositories.status(&entry.path)?;
+                Some(status.paint(cli.colors.as_ref()))
+            });
+        let Some(metadata_entry) = entry.metadata else {
+            // Just like GNU everything but the type is a question mark when the entry can't be
+            // read, like a broken link with -L
+            let file_type = entry
+                .path
+                .symlink_metadata()
+                .map(|metadata| metadata.permissions().mode_struct().file_type())
+                .unwrap_or('?');
+            let unknown = String::from("?");
+            entries.push(EntryItem {
+                mode: format!("{}?????????", file_type),
+                nlink: unknown.clone(),
+                owner: (!cli.list_no_owner).then(|| Id::name(unknown.clone())),
+                group: (!cli.no_group).then(|| Id::name(unknown.clone())),
+                size: unknown.clone(),
+                device: None,
+                blocks: unknown.clone(),
+                timestamps: format!("{: >width$}", "?", width = unknown_time_width),
+                processed_entry: entry.text,
+                name_range: entry.name_range,
+                inode: unknown.clone(),
+                author: cli.author.then(|| Id::name(unknown.clone())),
+                git,
+            });
+            continue;
+        };
+
+        // Get the permission string (Example: -rw-r--r--, octal 644)
+        let perms = metadata_entry.permissions().mode_struct();
+        let usage = usage(cli, &entry.path, &metadata_entry);
+        // Get owner and group, the ids are shown instead with -n or if they have no name
+        let owner = match cli.numeric_list {
+            true => Id::number(metadata_entry.uid()),
+            false => metadata_entry
+                .owner()
+                .map(Id::name)
+                .unwrap_or(Id::number(metadata_entry.uid())),
+        };
+        let group = match cli.numeric_list {
+            true => Id::number(metadata_entry.gid()),
+            false => metadata_entry
+                .group()
+                .map(Id::name)
+                .unwrap_or(Id::number(metadata_entry.gid())),
+        };
+
+        // Create timestamps
+        let file_timestamp = FileTimestamps::new(&metadata_entry);
+
+        // Now choose the right time to display
+        let timestamp =
+            match file_timestamp.get(cli.time_display_sort.unwrap_or(TimeWord::ModifiedTime)) {
+                Some(timestamp) => time_style.format(&timestamp.datetime, &now),
+                // Not every filesystem records when a file was born, GNU shows a question mark instead
+                None => format!("{: >width$}", "?", width = unknown_time_width),
+            };
+
+        // Finally create the format string
+        let entry_item = EntryItem {
+            mode: perms.to_string(),
+            nlink: metadata_entry.nlink().to_string(),
+            owner: (!cli.list_no_owner).then(|| owner.clone()),
+            group: (!cli.no_group).then_some(group),
+            size: cli.file_block_size.format(usage.apparent, 1),
+            device: device_numbers(&metadata_entry),
+            blocks: cli.output_block_size.format(usage.blocks, 512),
+            timestamps: timestamp,
+            name_range: entry.name_range,
+            processed_entry: match metadata_entry.file_type().is_symlink() {
+                true => [entry.text, link_target(cli, &entry.path)].concat(),
+                false => entry.text,
+            },
+            inode: metadata_entry.ino().to_string(),
+            // This is hilarious, but the author is just the owner. Why does this option even
+            // exist?
+            author: cli.author.then_some(owner),
+            git,
+        };
+        entries.push(entry_item);
+    }
+
+    // Device numbers are aligned on the comma, so the majors and minors line up separately
+    let longest_major = entries
+        .iter()
+        .filter_map(|x| x.device.as_ref().map(|(major, _)| major.len()))
+        .max()
+        .unwrap_or(0);
+    let longest_minor = entries
+        .iter()
+        .filter_map(|x| x.device.as_ref().map(|(_, minor)| minor.len()))
+        .max()
+        .unwrap_or(0);
+
+    // All "longest-variables"
+    let longest = Longest {
+        longest_nlink: entries.iter().map(|x| x.nlink.len()).max().unwrap_or(0),
+        longest_owner: entries
+            .iter()
+            .filter_map(|x| x.owner.as_ref().map(|owner| owner.text.width()))
+            .max()
+            .unwrap_or(0),
+        longest_group: entries
+            .iter()
+            .filter_map(|x| x.group.as_ref().map(|group| group.text.width()))
+            .max()
+            .unwrap_or(0),
+        longest_size: entries
+            .iter()
+            .map(|x| match x.device {
+                Some(_) => longest_major + 2 + longest_minor,
+                None => x.size.chars().count(),
+            })
+            .max()
+            .unwrap_or(0),
+        longest_inode: entries
+            .clone()
+            .iter()
+            .map(|x| x.inode.chars().count())
+            .max()
+            .unwrap_or(0),
+        longest_blocks: entries
+            .iter()
+            .map(|x| x.blocks.chars().count())
+            .max()
+            .unwrap_or(0),
+        longest_author: entries
+            .iter()
+            .filter_map(|x| x.author.as_ref().map(|author| author.text.width()))
+            .max()
+            .unwrap_or(0),
+        // Only relative times differ in width, GNU doesn't pad custom formats either
+        longest_time: if time_style == TimeStyle::Relative {
+            entries
+                .iter()
+                .map(|x| x.timestamps.chars().count())
+                .max()
+                .unwrap_or(0)
+        } else {
+            0
+        },
+    };
+
+    // The git column is left out when nothing is in a repository
+    let show_git = entries.iter().any(|x| x.git.is_some());
+
+    entries.iter().for_each(|f| {
+        let mut prefix = String::new();
+        if cli.inode {
+            prefix += &format!("{: >width$} ", f.inode, width = longest.longest_inode);
+        }
+        if cli.size_blocks {
+            prefix += &format!("{: >width$} ", f.blocks, width = longest.longest_blocks);
+        }
+        let size = match &f.device {
+            // Any extra room from wider sizes goes in front of the major, just like GNU
+            Some((major, minor)) => format!(
+                "{: >major_width$}, {: >minor_width$}",
+                major,
+                minor,
+                major_width = longest.longest_size - 2 - longest_minor,
+                minor_width = longest_minor
+            ),
+            None => f.size.clone(),
+        };
+        let mut ids = String::new();
+        for (id, width) in [
+            (&f.owner, longest.longest_owner),
+            (&f.group, longest.longest_group),
+            (&f.author, longest.longest_author),
+        ] {
+            if let Some(id) = id {
+                ids += &id.pad(width);
+                ids.push(' ');
+            }
+        }
+        let mut line = format!(
+            "{}{} {: >longest_dir$} {}{: >longest_size$} {: >longest_time$} ",
+            prefix,
+            f.mode,
+            f.nlink,
+            ids,
+            size,
+            f.timestamps,
+            longest_dir = longest.longest_nlink,
+            longest_size = longest.longest_size,
+            longest_time = longest.longest_time
+        );
+        if show_git {
+            line += f.git.as_deref().unwrap_or("--");
+            line.push(' ');
+        }
+        match &cli.dired_output {
+            Some(dired) => {
+                dired.print("  ");
+                dired.print(&line);
+                dired.print_entry(&f.processed_entry, f.name_range);
+                dired.print("\n");
+            }
+            None => {
+                print!("{}", line);
+                print_bytes(&f.processed_entry);
+                print!("{}", if cli.end_nul { "\0" } else { "\n" });
+            }
+        }
+    });
+}
+
+/// Quotes the name with the chosen style and reports whether that changed it. Characters in
+/// `quote_these_too` are quoted as well, on top of the ones which look like the indicators
+fn quote_name(cli: &Cli, name: &OsStr, quote_these_too: &[u8]) -> (Vec<u8>, bool) {
+    let style = cli.quoting_style.unwrap_or(QuotingWord::Literal);
+    let mut quote_these_too = quote_these_too.to_vec();
+    if style == QuotingWord::Escape {
+        quote_these_too.push(b' ');
+    }
+    match cli.indicator_style {
+        // GNU really quotes '*' only when it isn't an indicator
+        Some(IndicatorWord::FileType) => quote_these_too.extend_from_slice(b"*=>@|"),
+        Some(IndicatorWord::Classify) => quote_these_too.extend_from_slice(b"=>@|"),
+        _ => {}
+    }
+
+    let mut quoted = quote(name.as_bytes(), style, &quote_these_too);
+    let changed = quoted.first() != name.as_bytes().first() || quoted.len() != name.len();
+    if cli.hide_control_chars {
+        quoted = quoting::hide_control_chars(&quoted);
+    }
+    // Only the literal styles can leave bytes which aren't UTF-8, like GNU those are printed as
+    // they are
+    (quoted, changed)
+}
+
+/// Links the name to the file with --hyperlink. The outer quotes of a quoted name are left out of
+/// the link when names are aligned by their quotes, so the links line up as well
+fn link_name(cli: &Cli, path: &Path, name: &[u8], skip_quotes: bool) -> Vec<u8> {
+    let Some(host) = &cli.hyperlink_host else {
+        return name.to_vec();
+    };
+    if skip_quotes && name.len() >= 2 {
+        let (open, close) = (&name[..1], &name[name.len() - 1..]);
+        [
+            open,
+            &hyperlink::link(host, path, &name[1..name.len() - 1]),
+            close,
+        ]
+        .concat()
+    } else {
+        hyperlink::link(host, path, name)
+    }
+}
+
+/// The major and minor number of character and block devices
+fn device_numbers(metadata: &Metadata) -> Option<(String, String)> {
+    let file_type = metadata.file_type();
+    if !file_type.is_char_device() && !file_type.is_block_device() {
+        return None;
+    }
+    Some((
+        major(metadata.rdev()).to_string(),
+        minor(metadata.rdev()).to_string(),
+    ))
+}
+
+/// The character which --indicator-style appends to a name, empty if the style doesn't mark
+/// entries of this type. Entries which couldn't be read get none
+fn indicator(cli: &Cli, metadata: Option<&Metadata>) -> &'static str {
+    let style = cli.indicator_style.unwrap_or(IndicatorWord::None);
+    let Some(metadata) = metadata.filter(|_| style != IndicatorWord::None) else {
+        return "";
+    };
+    let file_type = metadata.file_type();
+    if file_type.is_file() {
+        let executable = metadata.mode() & (S_IXUSR | S_IXGRP | S_IXOTH) != 0;
+        return if style == IndicatorWord::Classify && executable {
+            "*"
+        } else {
+            ""
+        };
+    }
+    if file_type.is_dir() {
+        "/"
+    } else if style == IndicatorWord::Slash {
+        ""
+    } else if file_type.is_symlink() {
+        "@"
+    } else if file_type.is_fifo() {
+        "|"
+    } else if file_type.is_socket() {
+        "="
+    } else {
+        ""
+    }
+}
+
+/// The " -> target" part of a symlink in long listings, coloured like the file it points to and
+/// followed by its indicator, except with -p
+fn link_target(cli: &Cli, path: &Path) -> Vec<u8> {
+    let Ok(target) = read_link(path) else {
+        return vec![];
+    };
+    let target_name = target.to_string_lossy();
+    let target_text = link_name(
+        cli,
+        path,
+        &quote_name(cli, target.as_os_str(), b"").0,
+        false,
+    );
+    let metadata = path.metadata().ok();
+    let indicator = match cli.indicator_style {
+        Some(IndicatorWord::FileType | IndicatorWord::Classify) => {
+            indicator(cli, metadata.as_ref())
+        }
+        _ => "",
+    };
+    let target_text = match &cli.colors {
+        Some(colors) => colors.paint(
+            &target_text,
+            colors.target_sequence(&target_name, metadata.as_ref()),
+        ),
+        None => target_text,
+    };
+    [b" -> ", target_text.as_slice(), indicator.as_bytes()].concat()
+}
+
+/// Prints the bytes as they are, so names which aren't UTF-8 come out unchanged like with GNU
+fn print_bytes(bytes: &[u8]) {
+    stdout()
+        .write_all(bytes)
+        .expect("failed printing to stdout");
+}
+
+#[derive(Debug, Copy, Clone)]
+struct FileTimestamps {
+    access: Timestamp,
+    /// None if the filesystem doesn't report it
+    birth: Option<Timestamp>,
+    modified: Timestamp,
+    metadata_change: Timestamp,
+}
+
+impl FileTimestamps {
+    fn new(metadata: &Metadata) -> FileTimestamps {
+        FileTimestamps {
+            access: Timestamp::new(metadata.atime(), metadata.atime_nsec()),
+            // The standard library gets this from statx(2) with STATX_BTIME
+            birth: metadata.created().ok().map(|created| {
+                let (unix, nanos) = match created.duration_since(UNIX_EPOCH) {
+                    Ok(since) => (since.as_secs() as i64, since.subsec_nanos() as i64),
+                    Err(e) => {
+                        let before = e.duration();
+                        let nanos = before.subsec_nanos() as i64;
+                        if nanos == 0 {
+                            (-(before.as_secs() as i64), 0)
+                        } else {
+                            (-(before.as_secs() as i64) - 1, 1_000_000_000 - nanos)
+                        }
+                    }
+                };
+                Timestamp::new(unix, nanos)
+            }),
+            modified: Timestamp::new(metadata.mtime(), metadata.mtime_nsec()),
+            metadata_change: Timestamp::new(metadata.ctime(), metadata.ctime_nsec()),
+        }
+    }
+
+    fn get(&self, word: TimeWord) -> Option<Timestamp> {
+        match word {
+            TimeWord::AccessTime => Some(self.access),
+            TimeWord::BirthTime => self.birth,
+            TimeWord::MetadataChangeTime => Some(self.metadata_change),
+            TimeWord::ModifiedTime => Some(self.modified),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Timestamp {
+    unix: i64,
+    nanos: i64,
+    datetime: DateTime<Local>,
+}
+
+impl Timestamp {
+    fn new(unix: i64, nanos: i64) -> Timestamp {
+        Timestamp {
+            unix,
+            nanos,
+            datetime: Local
+                .timestamp_opt(unix, nanos as u32)
+                .single()
+                .unwrap_or(Local.timestamp_opt(0, 0).unwrap()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(arguments: &[&str]) -> ArgMatches {
+        Cli::command()
+            .try_get_matches_from([&["ls"], arguments].concat())
+            .unwrap()
+    }
+
+    #[test]
+    fn repeated_options_take_the_last_value() {
+        let matches = matches(&[
+            "-l",
+            "-l",
+            "--sort=size",
+            "--sort=time",
+            "--color=always",
+            "--color=never",
+            "-w",
+            "80",
+            "-w",
+            "100",
+            "-T",
+            "4",
+            "-T",
+            "2",
+        ]);
+        let cli = Cli::from_arg_matches(&matches).unwrap();
+        assert!(cli.list);
+        assert_eq!(cli.sort_word, Some(SortWord::Time));
+        assert_eq!(cli.color, Some(When::Never));
+        assert_eq!(cli.output_width, Some(100));
+        assert_eq!(cli.tab_size, Some(2));
+    }
+
+    #[test]
+    fn ignore_patterns_add_up() {
+        let matches = matches(&["-I", "a", "--ignore=b", "--hide", "c", "--hide=d"]);
+        let cli = Cli::from_arg_matches(&matches).unwrap();
+        assert_eq!(cli.ignore_pattern, ["a", "b"]);
+        assert_eq!(cli.hide, ["c", "d"]);
+    }
+
+    #[test]
+    fn later_formats_win() {
+        let format = |arguments: &[&str]| {
+            let matches = matches(arguments);
+            let cli = Cli::from_arg_matches(&matches).unwrap();
+            given_format(&matches, cli.format)
+        };
+        assert_eq!(format(&[]), None);
+        assert_eq!(format(&["-l", "-C"]), Some(FormatWord::Vertical));
+        assert_eq!(
+            format(&["--format=long", "--format=commas"]),
+            Some(FormatWord::Commas)
+        );
+        assert_eq!(format(&["-C", "-1"]), Some(FormatWord::SingleColumn));
+        assert_eq!(format(&["-1", "-x"]), Some(FormatWord::Across));
+        assert_eq!(
+            format(&["-l", "--format=single-column"]),
+            Some(FormatWord::SingleColumn)
+        );
+    }
+
+    #[test]
+    fn one_line_does_not_replace_long_formats() {
+        let format = |arguments: &[&str]| {
+            let matches = matches(arguments);
+            let cli = Cli::from_arg_matches(&matches).unwrap();
+            given_format(&matches, cli.format)
+        };
+        assert_eq!(format(&["-l", "-1"]), Some(FormatWord::Long));
+        assert_eq!(format(&["-1", "-l"]), Some(FormatWord::Long));
+        assert_eq!(format(&["-g", "-1"]), Some(FormatWord::Long));
+        assert_eq!(
+            format(&["--format=verbose", "-1"]),
+            Some(FormatWord::Verbose)
+        );
+        assert_eq!(format(&["-l", "-C", "-1"]), Some(FormatWord::SingleColumn));
+    }
+
+    #[test]
+    fn classify_never_keeps_earlier_styles() {
+        let style = |arguments: &[&str], terminal: bool| {
+            let matches = matches(arguments);
+            let cli = Cli::from_arg_matches(&matches).unwrap();
+            given_indicator_style(&matches, &cli, terminal)
+        };
+        assert_eq!(style(&[], false), None);
+        assert_eq!(style(&["--classify=never"], false), None);
+        assert_eq!(
+            style(&["-F", "--classify=never"], false),
+            Some(IndicatorWord::Classify)
+        );
+        assert_eq!(
+            style(&["-p", "--classify=auto"], false),
+            Some(IndicatorWord::Slash)
+        );
+        assert_eq!(
+            style(&["-p", "--classify=auto"], true),
+            Some(IndicatorWord::Classify)
+        );
+        assert_eq!(
+            style(&["--classify=auto", "--classify=always"], false),
+            Some(IndicatorWord::Classify)
+        );
+        assert_eq!(style(&["-F", "-p"], false), Some(IndicatorWord::Slash));
+        assert_eq!(
+            style(&["-p", "-F", "--indicator-style=none"], false),
+            Some(IndicatorWord::None)
+        );
+    }
+}
@@ -0,0 +1,587 @@
+//! Quoting of entry names, a port of gnulib's quotearg which GNU ls uses for --quoting-style
+
+use std::{env::var, sync::OnceLock};
+
+use unicode_width::UnicodeWidthChar;
+
+use super::QuotingWord;
+
+/// Quotes the name in the given style. Characters in `quote_these_too` are escaped as well, or
+/// force the outer quotes in the shell styles
+pub fn quote(name: &[u8], style: QuotingWord, quote_these_too: &[u8]) -> Vec<u8> {
+    restyled(name, style, false, quote_these_too)
+}
+
+/// Replaces every character which can't be printed with a question mark, like -q does
+pub fn hide_control_chars(quoted: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(quoted.len());
+    let mut i = 0;
+    while i < quoted.len() {
+        let (length, printable) = next_char(&quoted[i..]);
+        if printable {
+            result.extend_from_slice(&quoted[i..i + length]);
+        } else {
+            result.push(b'?');
+        }
+        i += length;
+    }
+    result
+}
+
+/// Columns the quoted name takes up in the terminal, counted like gnulib's mbsnwidth. Control
+/// characters take up none. In UTF-8 locales every invalid byte counts as one column and so does
+/// a character which is cut off at the end, elsewhere only the printable ASCII characters count
+pub fn width(quoted: &[u8]) -> usize {
+    if !utf8_locale() {
+        return quoted.iter().filter(|c| (0x20..0x7f).contains(*c)).count();
+    }
+    let mut width = 0;
+    let mut chunks = quoted.utf8_chunks().peekable();
+    while let Some(chunk) = chunks.next() {
+        width += chunk
+            .valid()
+            .chars()
+            .map(|c| c.width().unwrap_or(0))
+            .sum::<usize>();
+        let invalid = chunk.invalid();
+        let cut_off = chunks.peek().is_none()
+            && std::str::from_utf8(invalid).is_err_and(|e| e.error_len().is_none());
+        width += if cut_off { 1 } else { invalid.len() };
+    }
+    width
+}
+
+/// Whether the locale uses UTF-8. Otherwise every byte above 127 is unprintable, just like in the
+/// C locale
+fn utf8_locale() -> bool {
+    static UTF8: OnceLock<bool> = OnceLock::new();
+    *UTF8.get_or_init(|| {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .find_map(|name| var(name).ok().filter(|value| !value.is_empty()))
+            .unwrap_or_default()
+            .to_lowercase();
+        locale.contains("utf-8") || locale.contains("utf8")
+    })
+}
+
+/// Length of the character at the start of the bytes and whether it can be printed. Invalid
+/// bytes are unprintable characters of their own, and like with mbrtowc so is a character which
+/// is cut off at the end
+fn next_char(bytes: &[u8]) -> (usize, bool) {
+    let c = bytes[0];
+    if c.is_ascii() || !utf8_locale() {
+        return (1, (0x20..0x7f).contains(&c));
+    }
+    let length = match c {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return (1, false),
+    };
+    if bytes.len() < length && bytes[1..].iter().all(|c| (0x80..0xc0).contains(c)) {
+        return (bytes.len(), false);
+    }
+    match bytes
+        .get(..length)
+        .and_then(|sequence| std::str::from_utf8(sequence).ok())
+    {
+        Some(sequence) => (length, !sequence.chars().any(|ch| ch.is_control())),
+        None => (1, false),
+    }
+}
+
+/// The quotes of the locale styles. Like GNU without translations, curly quotes in UTF-8 locales
+/// and plain ones otherwise
+fn locale_quotes(style: QuotingWord) -> (&'static [u8], &'static [u8]) {
+    if utf8_locale() {
+        ("\u{2018}".as_bytes(), "\u{2019}".as_bytes())
+    } else if style == QuotingWord::Clocale {
+        (b"\"", b"\"")
+    } else {
+        (b"'", b"'")
+    }
+}
+
+/// Port of quotearg_buffer_restyled. The shell styles are all handled as shell-always, they only
+/// differ in whether they elide the outer quotes and use backslash escapes. Whenever a name turns
+/// out to need quoting while they are elided, it starts over with the quotes forced on
+fn restyled(
+    arg: &[u8],
+    mut style: QuotingWord,
+    mut elide_outer_quotes: bool,
+    quote_these_too: &[u8],
+) -> Vec<u8> {
+    let mut out: Vec<u8> = vec![];
+    let mut backslash_escapes = false;
+    let mut quote_string: &[u8] = b"";
+    let mut encountered_single_quote = false;
+    let mut all_c_and_shell_quote_compat = true;
+    let mut pending_shell_escape_end = false;
+
+    match style {
+        QuotingWord::CMaybe | QuotingWord::C => {
+            if style == QuotingWord::CMaybe {
+                style = QuotingWord::C;
+                elide_outer_quotes = true;
+            }
+            if !elide_outer_quotes {
+                out.push(b'"');
+            }
+            backslash_escapes = true;
+            quote_string = b"\"";
+        }
+        QuotingWord::Escape => {
+            backslash_escapes = true;
+            elide_outer_quotes = false;
+        }
+        QuotingWord::Locale | QuotingWord::Clocale => {
+            let (left, right) = locale_quotes(style);
+            if !elide_outer_quotes {
+                out.extend_from_slice(left);
+            }
+            backslash_escapes = true;
+            quote_string = right;
+        }
+        QuotingWord::Shell
+        | QuotingWord::ShellEscape
+        | QuotingWord::ShellAlways
+        | QuotingWord::ShellEscapeAlways => {
+            if style == QuotingWord::ShellEscape {
+                backslash_escapes = true;
+            }
+            if matches!(style, QuotingWord::Shell | QuotingWord::ShellEscape) {
+                elide_outer_quotes = true;
+            }
+            if style != QuotingWord::ShellAlways && !elide_outer_quotes {
+                backslash_escapes = true;
+            }
+            style = QuotingWord::ShellAlways;
+            if !elide_outer_quotes {
+                out.push(b'\'');
+            }
+            quote_string = b"'";
+        }
+        QuotingWord::Literal => elide_outer_quotes = false,
+    }
+    let shell = style == QuotingWord::ShellAlways;
+
+    // Starts over with the outer quotes, which sufficiently quote the extra characters too
+    macro_rules! force_outer_quoting_style {
+        () => {
+            return restyled(
+                arg,
+                if shell && backslash_escapes {
+                    QuotingWord::ShellEscapeAlways
+                } else {
+                    style
+                },
+                false,
+                b"",
+            )
+        };
+    }
+
+    let mut i = 0;
+    while i < arg.len() {
+        let mut c = arg[i];
+        let mut is_right_quote = false;
+        let mut escaping = false;
+        let mut c_and_shell_quote_compat = false;
+
+        macro_rules! start_esc {
+            () => {
+                if elide_outer_quotes {
+                    force_outer_quoting_style!();
+                }
+                escaping = true;
+                if shell && !pending_shell_escape_end {
+                    out.extend_from_slice(b"'$'");
+                    pending_shell_escape_end = true;
+                }
+                out.push(b'\\');
+            };
+        }
+        macro_rules! end_esc {
+            () => {
+                if pending_shell_escape_end && !escaping {
+                    out.extend_from_slice(b"''");
+                    pending_shell_escape_end = false;
+                }
+            };
+        }
+
+        if backslash_escapes
+            && !shell
+            && !quote_string.is_empty()
+            && arg[i..].starts_with(quote_string)
+        {
+            if elide_outer_quotes {
+                force_outer_quoting_style!();
+            }
+            is_right_quote = true;
+        }
+
+        // Whether the character has to be escaped, None if that depends on `quote_these_too`
+        let escape: Option<bool> = match c {
+            b'?' => {
+                if shell && elide_outer_quotes {
+                    force_outer_quoting_style!();
+                }
+                None
+            }
+            0x07 | 0x08 | 0x0c | 0x0b | b'\n' | b'\r' | b'\t' | b'\\' => {
+                let esc = match c {
+                    0x07 => b'a',
+                    0x08 => b'b',
+                    0x0c => b'f',
+                    0x0b => b'v',
+                    b'\n' => b'n',
+                    b'\r' => b'r',
+                    b'\t' => b't',
+                    _ => b'\\',
+                };
+                if c == b'\\' && shell {
+                    // Never needs escaping inside of single quotes
+                    if elide_outer_quotes {
+                        force_outer_quoting_style!();
+                    }
+                    Some(false)
+                } else if c == b'\\'
+                    && backslash_escapes
+                    && elide_outer_quotes
+                    && !quote_string.is_empty()
+                {
+                    Some(false)
+                } else {
+                    if matches!(c, b'\n' | b'\r' | b'\t' | b'\\') && shell && elide_outer_quotes {
+                        force_outer_quoting_style!();
+                    }
+                    if backslash_escapes {
+                        c = esc;
+                        Some(true)
+                    } else {
+                        None
+                    }
+                }
+            }
+            b'{' | b'}' | b'#' | b'~' | b' ' => {
+                // Braces are only special on their own, and # and ~ at the start
+                let special = match c {
+                    b'{' | b'}' => arg.len() == 1,
+                    b'#' | b'~' => i == 0,
+                    _ => true,
+                };
+                if special {
+                    c_and_shell_quote_compat = true;
+                    if shell && elide_outer_quotes {
+                        force_outer_quoting_style!();
+                    }
+                }
+                None
+            }
+            b'!' | b'"' | b'$' | b'&' | b'(' | b')' | b'*' | b';' | b'<' | b'=' | b'>' | b'['
+            | b'^' | b'`' | b'|' => {
+                if shell && elide_outer_quotes {
+                    force_outer_quoting_style!();
+                }
+                None
+            }
+            b'\'' => {
+                encountered_single_quote = true;
+                c_and_shell_quote_compat = true;
+                if shell {
+                    if elide_outer_quotes {
+                        force_outer_quoting_style!();
+                    }
+                    out.extend_from_slice(b"'\\'");
+                    pending_shell_escape_end = false;
+                }
+                None
+            }
+            b'%'
+            | b'+'
+            | b','
+            | b'-'
+            | b'.'
+            | b'/'
+            | b'0'..=b'9'
+            | b':'
+            | b'A'..=b'Z'
+            | b']'
+            | b'_'
+            | b'a'..=b'z' => {
+                c_and_shell_quote_compat = true;
+                None
+            }
+            _ => {
+                // Multibyte characters are copied as a whole, unprintable ones are escaped byte
+                // by byte in the styles which use backslashes
+                let (length, printable) = next_char(&arg[i..]);
+                c_and_shell_quote_compat = printable;
+                if 1 < length || (backslash_escapes && !printable) {
+                    let last = i + length - 1;
+                    loop {
+                        if backslash_escapes && !printable {
+                            start_esc!();
+                            out.push(b'0' + (c >> 6));
+                            out.push(b'0' + ((c >> 3) & 7));
+                            c = b'0' + (c & 7);
+                        } else if is_right_quote {
+                            out.push(b'\\');
+                            is_right_quote = false;
+                        }
+                        if last <= i {
+                            break;
+                        }
+                        end_esc!();
+                        out.push(c);
+                        i += 1;
+                        c = arg[i];
+                    }
+                    Some(false)
+                } else {
+                    None
+                }
+            }
+        };
+
+        let escape = escape.unwrap_or_else(|| {
+            (((backslash_escapes && !shell) || elide_outer_quotes) && quote_these_too.contains(&c))
+                || is_right_quote
+        });
+        if escape {
+            start_esc!();
+        }
+        end_esc!();
+        out.push(c);
+        if !c_and_shell_quote_compat {
+            all_c_and_shell_quote_compat = false;
+        }
+        i += 1;
+    }
+
+    if out.is_empty() && shell && elide_outer_quotes {
+        force_outer_quoting_style!();
+    }
+
+    // Single quotes are common enough as apostrophes that double quotes are used instead if
+    // nothing else needs escaping
+    if shell && !elide_outer_quotes && encountered_single_quote && all_c_and_shell_quote_compat {
+        return restyled(arg, QuotingWord::C, false, quote_these_too);
+    }
+
+    if !elide_outer_quotes {
+        out.extend_from_slice(quote_string);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAMES: [&[u8]; 8] = [
+        b"plain",
+        b"a b",
+        b"it's",
+        b"tab\tx",
+        b"back\\slash",
+        b"~home",
+        b"it's \"both\"",
+        b"\xff",
+    ];
+
+    /// Quotes every name in `NAMES` with the style, and spaces too for the escape style just like
+    /// ls does
+    fn quote_all(style: QuotingWord) -> Vec<String> {
+        let quote_these_too: &[u8] = match style {
+            QuotingWord::Escape => b" ",
+            _ => b"",
+        };
+        NAMES
+            .iter()
+            .map(|name| String::from_utf8_lossy(&quote(name, style, quote_these_too)).into_owned())
+            .collect()
+    }
+
+    // The expected names are what GNU ls 9.1 prints with --quoting-style, which is the same in
+    // the C and UTF-8 locales for these names
+
+    #[test]
+    fn literal_and_escape() {
+        assert_eq!(
+            quote(b"tab\tx\xff", QuotingWord::Literal, b""),
+            b"tab\tx\xff"
+        );
+        assert_eq!(
+            quote_all(QuotingWord::Escape),
+            [
+                "plain",
+                "a\\ b",
+                "it's",
+                "tab\\tx",
+                "back\\\\slash",
+                "~home",
+                "it's\\ \"both\"",
+                "\\377"
+            ]
+        );
+    }
+
+    #[test]
+    fn shell_styles() {
+        assert_eq!(
+            quote_all(QuotingWord::Shell),
+            [
+                "plain",
+                "'a b'",
+                "\"it's\"",
+                "'tab\tx'",
+                "'back\\slash'",
+                "'~home'",
+                "'it'\\''s \"both\"'",
+                "\u{fffd}"
+            ]
+        );
+        assert_eq!(
+            quote_all(QuotingWord::ShellAlways),
+            [
+                "'plain'",
+                "'a b'",
+                "\"it's\"",
+                "'tab\tx'",
+                "'back\\slash'",
+                "'~home'",
+                "'it'\\''s \"both\"'",
+                "'\u{fffd}'"
+            ]
+        );
+        // A '~' or '#' is only special at the start, braces only on their own
+        assert_eq!(quote(b"x~", QuotingWord::Shell, b""), b"x~");
+        assert_eq!(quote(b"#hash", QuotingWord::Shell, b""), b"'#hash'");
+        assert_eq!(quote(b"x{", QuotingWord::Shell, b""), b"x{");
+        assert_eq!(quote(b"{", QuotingWord::Shell, b""), b"'{'");
+        assert_eq!(quote(b"-dash", QuotingWord::Shell, b""), b"-dash");
+        assert_eq!(quote(b"a?b", QuotingWord::Shell, b""), b"'a?b'");
+    }
+
+    #[test]
+    fn shell_escape_styles() {
+        assert_eq!(
+            quote_all(QuotingWord::ShellEscape),
+            [
+                "plain",
+                "'a b'",
+                "\"it's\"",
+                "'tab'$'\\t''x'",
+                "'back\\slash'",
+                "'~home'",
+                "'it'\\''s \"both\"'",
+                "''$'\\377'"
+            ]
+        );
+        assert_eq!(
+            quote_all(QuotingWord::ShellEscapeAlways),
+            [
+                "'plain'",
+                "'a b'",
+                "\"it's\"",
+                "'tab'$'\\t''x'",
+                "'back\\slash'",
+                "'~home'",
+                "'it'\\''s \"both\"'",
+                "''$'\\377'"
+            ]
+        );
+        assert_eq!(
+            quote(b"nl\nx", QuotingWord::ShellEscape, b""),
+            b"'nl'$'\\n''x'"
+        );
+    }
+
+    #[test]
+    fn c_styles() {
+        assert_eq!(
+            quote_all(QuotingWord::C),
+            [
+                "\"plain\"",
+                "\"a b\"",
+                "\"it's\"",
+                "\"tab\\tx\"",
+                "\"back\\\\slash\"",
+                "\"~home\"",
+                "\"it's \\\"both\\\"\"",
+                "\"\\377\""
+            ]
+        );
+        assert_eq!(
+            quote_all(QuotingWord::CMaybe),
+            [
+                "plain",
+                "a b",
+                "it's",
+                "\"tab\\tx\"",
+                "back\\slash",
+                "~home",
+                "\"it's \\\"both\\\"\"",
+                "\"\\377\""
+            ]
+        );
+    }
+
+    #[test]
+    fn locale_styles() {
+        let (left, right) = match utf8_locale() {
+            true => ("\u{2018}", "\u{2019}"),
+            false => ("'", "'"),
+        };
+        assert_eq!(
+            quote(b"a b", QuotingWord::Locale, b""),
+            format!("{}a b{}", left, right).as_bytes()
+        );
+        assert_eq!(
+            quote(b"tab\tx", QuotingWord::Locale, b""),
+            format!("{}tab\\tx{}", left, right).as_bytes()
+        );
+    }
+
+    #[test]
+    fn quote_these_too() {
+        // The headers of -R quote colons
+        assert_eq!(quote(b"a:b", QuotingWord::Shell, b":"), b"'a:b'");
+        assert_eq!(quote(b"a:b", QuotingWord::Escape, b":"), b"a\\:b");
+        assert_eq!(quote(b"a:b", QuotingWord::Literal, b":"), b"a:b");
+    }
+
+    #[test]
+    fn hidden_control_chars() {
+        assert_eq!(hide_control_chars(b"tab\tx\x7f"), b"tab?x?");
+        assert_eq!(hide_control_chars(b"inv\xffx"), b"inv?x");
+        if utf8_locale() {
+            assert_eq!(hide_control_chars("café".as_bytes()), "café".as_bytes());
+            // A character cut off at the end is one question mark, like GNU
+            assert_eq!(hide_control_chars(b"cut\xe2\x82"), b"cut?");
+            assert_eq!(hide_control_chars(b"mid\xe2\x82y"), b"mid??y");
+        } else {
+            assert_eq!(hide_control_chars("café".as_bytes()), b"caf??");
+        }
+    }
+
+    #[test]
+    fn widths() {
+        assert_eq!(width(b"tab\tname"), 7);
+        assert_eq!(width(b"bad\x7fname"), 7);
+        if utf8_locale() {
+            assert_eq!(width("café".as_bytes()), 4);
+            assert_eq!(width("\u{200b}zw".as_bytes()), 2);
+            assert_eq!(width("漢字".as_bytes()), 4);
+            assert_eq!(width(b"inv\xffx"), 5);
+            assert_eq!(width(b"cut\xe2\x82"), 4);
+            assert_eq!(width(b"mid\xe2\x82y"), 6);
+        } else {
+            assert_eq!(width("café".as_bytes()), 3);
+            assert_eq!(width(b"inv\xffx"), 4);
+        }
+    }
+}
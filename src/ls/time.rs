@@ -0,0 +1,117 @@
+//! Formatting of timestamps in long listings, picked with --time-style or TIME_STYLE
+
+use std::{fmt::Write, str::FromStr};
+
+use chrono::{format::StrftimeItems, DateTime, Local};
+
+/// GNU treats files as recent if they were modified in the last six months, or half of an
+/// average Gregorian year
+const SIX_MONTHS: i64 = 31556952 / 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimeStyle {
+    /// Formats for files older than six months and for recent files
+    Format { old: String, recent: String },
+    /// How long ago the file was touched, like "3 min ago"
+    Relative,
+}
+
+impl Default for TimeStyle {
+    fn default() -> Self {
+        TimeStyle::from_str("locale").unwrap()
+    }
+}
+
+impl FromStr for TimeStyle {
+    type Err = String;
+
+    fn from_str(style: &str) -> Result<Self, Self::Err> {
+        let mut style = style;
+        // "posix-" styles only apply outside of the POSIX locale, there the locale style is used
+        while let Some(rest) = style.strip_prefix("posix-") {
+            if super::c_locale("LC_TIME") {
+                style = "locale";
+                break;
+            }
+            style = rest;
+        }
+
+        let (old, recent) = match style {
+            "full-iso" => ("%Y-%m-%d %H:%M:%S.%N %z", "%Y-%m-%d %H:%M:%S.%N %z"),
+            "long-iso" => ("%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M"),
+            "iso" => ("%Y-%m-%d ", "%m-%d %H:%M"),
+            "locale" => ("%b %e  %Y", "%b %e %H:%M"),
+            "relative" => return Ok(TimeStyle::Relative),
+            _ => {
+                let Some(format) = style.strip_prefix('+') else {
+                    return Err(format!(
+                        "invalid argument '{}' for 'time style'\nValid arguments are:\n  - [posix-]full-iso\n  - [posix-]long-iso\n  - [posix-]iso\n  - [posix-]locale\n  - relative\n  - +FORMAT (e.g., +%H:%M) for a 'date'-style format",
+                        style
+                    ));
+                };
+                // "+FORMAT1\nFORMAT2" uses the first format for old files and the second for
+                // recent ones
+                match format.split_once('\n') {
+                    Some((old, recent)) if !recent.contains('\n') => (old, recent),
+                    Some(_) => return Err(format!("invalid time style format '{}'", format)),
+                    None => (format, format),
+                }
+            }
+        };
+        Ok(TimeStyle::Format {
+            old: old.to_string(),
+            recent: recent.to_string(),
+        })
+    }
+}
+
+impl TimeStyle {
+    pub fn format(&self, time: &DateTime<Local>, now: &DateTime<Local>) -> String {
+        let (old, recent) = match self {
+            TimeStyle::Format { old, recent } => (old, recent),
+            TimeStyle::Relative => return relative(time, now),
+        };
+        let age = now.timestamp() - time.timestamp();
+        let format = if (0..SIX_MONTHS).contains(&age) {
+            recent
+        } else {
+            old
+        };
+
+        // chrono has no %N, it calls the nanoseconds %9f
+        let format = format.replace("%N", "%9f");
+        let mut result = String::new();
+        match write!(
+            result,
+            "{}",
+            time.format_with_items(StrftimeItems::new(&format))
+        ) {
+            Ok(_) => result,
+            // Unknown conversions are left alone instead of failing the whole listing
+            Err(_) => format,
+        }
+    }
+}
+
+fn relative(time: &DateTime<Local>, now: &DateTime<Local>) -> String {
+    let seconds = now.timestamp() - time.timestamp();
+    let (amount, unit) = match seconds.abs() {
+        s if s < 60 => (s, "sec"),
+        s if s < 60 * 60 => (s / 60, "min"),
+        s if s < 60 * 60 * 24 => (s / (60 * 60), "hour"),
+        s if s < 60 * 60 * 24 * 7 => (s / (60 * 60 * 24), "day"),
+        s if s < 60 * 60 * 24 * 30 => (s / (60 * 60 * 24 * 7), "week"),
+        s if s < 60 * 60 * 24 * 365 => (s / (60 * 60 * 24 * 30), "month"),
+        s => (s / (60 * 60 * 24 * 365), "year"),
+    };
+    let plural = if amount != 1 && unit != "sec" && unit != "min" {
+        "s"
+    } else {
+        ""
+    };
+    if seconds < 0 {
+        format!("in {} {}{}", amount, unit, plural)
+    } else {
+        format!("{} {}{} ago", amount, unit, plural)
+    }
+}
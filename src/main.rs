@@ -2,7 +2,7 @@ const MODULES: &str = include_str!("metadata/modules");
 const VERSION: &str = include_str!("metadata/version");
 const BUILD: &str = include_str!("metadata/build");
 
-use std::{env::args, process::exit};
+use std::{env::args_os, ffi::OsString, path::Path, process::exit};
 // All modules
 mod cat;
 mod chmod;
@@ -19,8 +19,9 @@ mod utils;
 // End of modules
 
 fn main() {
-    let arguments: Vec<String> = args().collect();
-    let mut command = arguments[0].split("/").last().unwrap();
+    // Arguments which aren't UTF-8 are left to the commands, like file names for ls
+    let arguments: Vec<OsString> = args_os().collect();
+    let mut command = Path::new(&arguments[0]).file_name().unwrap_or_default();
     if arguments.len() < 2 && command == "blutils" {
         help();
     } else if arguments.len() >= 2
@@ -29,9 +30,9 @@ fn main() {
     {
         list();
     } else if command == "blutils" {
-        command = arguments[1].as_str();
+        command = arguments[1].as_os_str();
     };
-    match command {
+    match command.to_str().unwrap_or_default() {
         "ls" => ls::main(),
         "cat" => cat::main(),
         "mkdir" => mkdir::main(),
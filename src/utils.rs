@@ -1,16 +1,21 @@
 use libc::{
-    getuid, S_IFIFO, S_IFSOCK, S_IRGRP, S_IROTH, S_IRUSR, S_ISVTX, S_IWGRP, S_IWOTH, S_IWUSR, S_IXGRP, S_IXOTH, S_IXUSR
+    getuid, S_IFBLK, S_IFCHR, S_IFDIR, S_IFIFO, S_IFLNK, S_IFMT, S_IFREG, S_IFSOCK, S_IRGRP, S_IROTH,
+    S_IRUSR, S_ISGID, S_ISUID, S_ISVTX, S_IWGRP, S_IWOTH, S_IWUSR, S_IXGRP, S_IXOTH, S_IXUSR,
 };
 use std::{
     any::Any,
-    ffi::CString,
+    cmp::Ordering,
+    collections::HashSet,
+    env::var_os,
     fmt::Display,
-    fs::{Metadata, Permissions},
+    fs::{read_dir, Metadata, Permissions},
     io::{Error, Read, Result},
     os::unix::fs::{MetadataExt, PermissionsExt},
     path::Path,
     process::exit,
+    str::FromStr,
 };
+use users::{Groups, Users, UsersCache};
 
 pub fn log<T: Display>(verbose: bool, message: T) {
     if verbose {
@@ -42,50 +47,325 @@ pub fn libc_wrap<T: Ord + Default>(num: T) -> Result<T> {
     Ok(num)
 }
 
-pub fn c_escape(contents: String, show_tabs: bool) -> String {
-    let mut result = String::new();
-    for ch in contents.chars() {
-        // Make sure it isnt a control code
-        if ch as u8 >= 32 {
-            if 127 > ch as u8 {
-                // Printable char, just push it to result string
-                result.push(ch);
-            } else if ch as u8 == 127 {
-                // Del char
-                result.push_str("^?");
-            } else {
-                // Meta characters
-                result.push_str("M-");
-                if 128 + 32 <= ch as u8 {
-                    if 128 + 127 > ch as u8 {
-                        // Meta character is out of the ascii range, we remove 128 to make it
-                        // printable
-                        result.push((ch as u8 - 128) as char)
-                    } else {
-                        result.push_str("^?");
+/// Length of the name without its file suffix, where a suffix is the longest match of
+/// `(\.[A-Za-z~][A-Za-z0-9~]*)*$`. Port of gnulib's file_prefixlen as GNU coreutils 9.1 has it,
+/// where the suffix may start at the very beginning, so all of ".bashrc" is a suffix
+fn file_prefixlen(s: &[u8]) -> usize {
+    let mut i = 0;
+    loop {
+        let prefixlen = i;
+        while i + 1 < s.len()
+            && s[i] == b'.'
+            && (s[i + 1].is_ascii_alphabetic() || s[i + 1] == b'~')
+        {
+            i += 2;
+            while i < s.len() && (s[i].is_ascii_alphanumeric() || s[i] == b'~') {
+                i += 1;
+            }
+        }
+        if i >= s.len() {
+            return prefixlen;
+        }
+        i += 1;
+    }
+}
+
+/// Sort weight of the byte at `pos`. Letters sort before other characters, '~' sorts before
+/// everything, even the end of the string
+fn version_order(s: &[u8], pos: usize) -> i32 {
+    match s.get(pos) {
+        None => -1,
+        Some(c) if c.is_ascii_digit() => 0,
+        Some(c) if c.is_ascii_alphabetic() => *c as i32,
+        Some(b'~') => -2,
+        Some(c) => *c as i32 + 256,
+    }
+}
+
+/// The Debian version comparison algorithm, port of gnulib's verrevcmp
+fn verrevcmp(s1: &[u8], s2: &[u8]) -> Ordering {
+    let (mut s1_pos, mut s2_pos) = (0, 0);
+    while s1_pos < s1.len() || s2_pos < s2.len() {
+        let mut first_diff = 0;
+        while (s1_pos < s1.len() && !s1[s1_pos].is_ascii_digit())
+            || (s2_pos < s2.len() && !s2[s2_pos].is_ascii_digit())
+        {
+            let s1_c = version_order(s1, s1_pos);
+            let s2_c = version_order(s2, s2_pos);
+            if s1_c != s2_c {
+                return s1_c.cmp(&s2_c);
+            }
+            s1_pos += 1;
+            s2_pos += 1;
+        }
+        while s1_pos < s1.len() && s1[s1_pos] == b'0' {
+            s1_pos += 1;
+        }
+        while s2_pos < s2.len() && s2[s2_pos] == b'0' {
+            s2_pos += 1;
+        }
+        while s1_pos < s1.len()
+            && s2_pos < s2.len()
+            && s1[s1_pos].is_ascii_digit()
+            && s2[s2_pos].is_ascii_digit()
+        {
+            if first_diff == 0 {
+                first_diff = s1[s1_pos] as i32 - s2[s2_pos] as i32;
+            }
+            s1_pos += 1;
+            s2_pos += 1;
+        }
+        if s1_pos < s1.len() && s1[s1_pos].is_ascii_digit() {
+            return Ordering::Greater;
+        }
+        if s2_pos < s2.len() && s2[s2_pos].is_ascii_digit() {
+            return Ordering::Less;
+        }
+        if first_diff != 0 {
+            return first_diff.cmp(&0);
+        }
+    }
+    Ordering::Equal
+}
+
+/// Compares two file names as version numbers, so "file9" comes before "file10". Works exactly
+/// like GNU's filevercmp, which is what `ls -v` and `sort -V` use
+pub fn filevercmp(a: &[u8], b: &[u8]) -> Ordering {
+    // Empty names first
+    if a.is_empty() || b.is_empty() {
+        return a.len().cmp(&b.len());
+    }
+
+    // "." sorts first, then "..", then other hidden files and after that everything else
+    if a[0] == b'.' {
+        if b[0] != b'.' {
+            return Ordering::Less;
+        }
+        for special in [&b"."[..], &b".."[..]] {
+            match (a == special, b == special) {
+                (true, true) => return Ordering::Equal,
+                (true, false) => return Ordering::Less,
+                (false, true) => return Ordering::Greater,
+                _ => (),
+            }
+        }
+    } else if b[0] == b'.' {
+        return Ordering::Greater;
+    }
+
+    // Compare without the file suffixes first, and only look at them if that is a tie
+    let a_prefix = file_prefixlen(a);
+    let b_prefix = file_prefixlen(b);
+    let result = verrevcmp(&a[..a_prefix], &b[..b_prefix]);
+    if result != Ordering::Equal || (a_prefix == a.len() && b_prefix == b.len()) {
+        return result;
+    }
+    verrevcmp(a, b)
+}
+
+/// Unit letters for every power of the base, the first one has no letter
+const UNIT_LETTERS: [&str; 11] = ["", "K", "M", "G", "T", "P", "E", "Z", "Y", "R", "Q"];
+
+/// How sizes are scaled before printing them. Parsed from SIZE arguments like GNU's
+/// `--block-size`, so "M", "1M", "10KB", "MiB", "human-readable" and "si" all work
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockSize {
+    /// Bytes in one output block, 1 when autoscaling
+    size: u64,
+    /// Pick the largest unit which keeps the number below the base, like -h and --si
+    autoscale: bool,
+    /// Units are powers of 1024 instead of 1000
+    base_1024: bool,
+    /// Print the unit letter after the number, GNU only does so if SIZE had no number
+    suffix: bool,
+    /// Follow the unit letter by "B", or "iB" for powers of 1024
+    bytes_suffix: bool,
+}
+
+impl Default for BlockSize {
+    /// 1024 byte blocks, or 512 byte blocks if POSIXLY_CORRECT is set
+    fn default() -> Self {
+        BlockSize::new(if var_os("POSIXLY_CORRECT").is_some() {
+            512
+        } else {
+            1024
+        })
+    }
+}
+
+impl FromStr for BlockSize {
+    type Err = String;
+
+    fn from_str(spec: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || format!("invalid block size '{}'", spec);
+        // A leading ' asks for thousands separators, which the C locale doesn't have
+        let size_spec = spec.strip_prefix('\'').unwrap_or(spec);
+        match size_spec {
+            "human-readable" => return Ok(BlockSize::human(true)),
+            "si" => return Ok(BlockSize::human(false)),
+            _ => (),
+        }
+
+        let digits = size_spec.len()
+            - size_spec
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .len();
+        let (number, unit) = size_spec.split_at(digits);
+        let number: u64 = if number.is_empty() {
+            1
+        } else {
+            number.parse().map_err(|_| invalid())?
+        };
+
+        let mut unit_chars = unit.chars();
+        let (exponent, base_1024, bytes_suffix) = match unit_chars.next() {
+            None => (0, true, false),
+            Some(letter) => {
+                let exponent = UNIT_LETTERS
+                    .iter()
+                    .position(|u| u.eq_ignore_ascii_case(&letter.to_string()))
+                    .filter(|exponent| *exponent != 0)
+                    .ok_or_else(invalid)?;
+                match unit_chars.as_str() {
+                    "" => (exponent, true, false),
+                    "B" => (exponent, false, true),
+                    "iB" => (exponent, true, true),
+                    _ => return Err(invalid()),
+                }
+            }
+        };
+        let base: u64 = if base_1024 { 1024 } else { 1000 };
+        let size = base
+            .checked_pow(exponent as u32)
+            .and_then(|unit| unit.checked_mul(number))
+            .filter(|size| *size != 0)
+            .ok_or_else(invalid)?;
+
+        Ok(BlockSize {
+            size,
+            autoscale: false,
+            base_1024,
+            suffix: digits == 0 && !unit.is_empty(),
+            bytes_suffix,
+        })
+    }
+}
+
+impl BlockSize {
+    /// Plain blocks of `size` bytes, printed without a unit
+    pub fn new(size: u64) -> BlockSize {
+        BlockSize {
+            size,
+            autoscale: false,
+            base_1024: true,
+            suffix: false,
+            bytes_suffix: false,
+        }
+    }
+
+    /// Human readable sizes like 1.5K or 234M, in powers of 1024 (-h) or 1000 (--si)
+    pub fn human(base_1024: bool) -> BlockSize {
+        BlockSize {
+            size: 1,
+            autoscale: true,
+            base_1024,
+            suffix: true,
+            bytes_suffix: false,
+        }
+    }
+
+    /// Formats `amount` blocks of `from_block` bytes each, rounding up just like GNU does. Numbers
+    /// below 10 get one decimal when autoscaling
+    pub fn format(&self, amount: u64, from_block: u64) -> String {
+        let base: u128 = if self.base_1024 { 1024 } else { 1000 };
+        let max_exponent = UNIT_LETTERS.len() - 1;
+        let bytes = amount as u128 * from_block as u128;
+        let to_block = self.size as u128;
+
+        // Rounding is 0 if nothing was cut off, 1 if less than half, 2 if exactly half and 3 if
+        // more than half
+        let mut value = bytes / to_block;
+        let r10 = (bytes % to_block) * 10;
+        let r2 = (r10 % to_block) * 2;
+        let mut tenths = r10 / to_block;
+        let mut rounding = if r2 < to_block {
+            u128::from(0 < r2)
+        } else {
+            2 + u128::from(to_block < r2)
+        };
+
+        let mut exponent = 0;
+        let mut fraction = String::new();
+        if self.autoscale && base <= value {
+            while base <= value && exponent < max_exponent {
+                let r10 = (value % base) * 10 + tenths;
+                let r2 = (r10 % base) * 2 + (rounding >> 1);
+                value /= base;
+                tenths = r10 / base;
+                rounding = if r2 < base {
+                    u128::from(r2 + rounding != 0)
+                } else {
+                    2 + u128::from(base < r2 + rounding)
+                };
+                exponent += 1;
+            }
+
+            if value < 10 {
+                if 0 < rounding {
+                    tenths += 1;
+                    rounding = 0;
+                    if tenths == 10 {
+                        value += 1;
+                        tenths = 0;
                     }
+                }
+                if value < 10 {
+                    fraction = format!(".{}", tenths);
+                    tenths = 0;
+                    rounding = 0;
+                }
+            }
+        }
+
+        if 0 < tenths + rounding {
+            value += 1;
+            if self.autoscale && value == base && exponent < max_exponent {
+                exponent += 1;
+                fraction = String::from(".0");
+                value = 1;
+            }
+        }
+
+        let mut result = format!("{}{}", value, fraction);
+        if self.suffix {
+            if !self.autoscale {
+                let mut power = 1;
+                while power < to_block && exponent < max_exponent {
+                    power *= base;
+                    exponent += 1;
+                }
+            }
+            if exponent != 0 {
+                result.push_str(if !self.base_1024 && exponent == 1 {
+                    "k"
                 } else {
-                    result.push('^');
-                    result.push((ch as u8 - 128 + 64) as char);
+                    UNIT_LETTERS[exponent]
+                });
+            }
+            if self.bytes_suffix {
+                if self.base_1024 && exponent != 0 {
+                    result.push('i');
                 }
+                result.push('B');
             }
-        } else if ch == '\t' && !show_tabs {
-            result.push(ch)
-        } else if ch == '\n' {
-            result.push(ch)
-        } else {
-            // If it is a control code we push ^, and add 64 to the char to its printable
-            result.push('^');
-            result.push((ch as u8 + 64) as char);
         }
+        result
     }
-    result
 }
 
 pub trait PathExtras {
     fn type_display(&self) -> Box<dyn Display>;
     fn ptype(&self) -> PathType;
-    fn str_classify(&self, no_exe: bool, when: i8) -> String;
 }
 
 pub fn test_mode(m: Metadata, t: u32) -> bool {
@@ -132,29 +412,6 @@ impl PathExtras for Path {
 
         PathType::File
     }
-    fn str_classify(&self, no_exe: bool, when: i8) -> String {
-        let result = match self.ptype() {
-            PathType::Symlink => "@",
-            PathType::Directory => "/",
-            PathType::Executable => if !no_exe { "*" } else { " " },
-            PathType::Door => ">",
-            PathType::FIFO => "|",
-            PathType::Socket => "=",
-            _ => " ",
-        }
-        .to_string();
-        match when {
-            0 => result,
-            1 => {
-                if termsize::get().is_some() {
-                    result
-                } else {
-                    " ".to_string()
-                }
-            }
-            _ => " ".to_string(),
-        }
-    }
 }
 
 pub enum PathType {
@@ -167,46 +424,166 @@ pub enum PathType {
     Door,
 }
 
-pub trait MetadataPlus {
-    fn owner(&self) -> String;
-    fn group(&self) -> String;
-}
-
-/*impl MetadataPlus for Metadata {
-    fn group(&self) -> String {
-        let group: String;
-        unsafe {
-            dbg!(self.gid());
-            let group_entry_pointer = getgrgid(self.gid());
-            // Check so pointer is not null
-            if group_entry_pointer.is_null() {
-                group = String::from("unknown");
-                return group;
-            };
-            let group_entry = group_entry_pointer.read();
-            dbg!(&group_entry);
-            let group_raw = CString::from_raw(group_entry.gr_name);
-            group = group_raw.to_str().unwrap_or("unknown").to_string();
+impl PathType {
+    /// The type of the file the metadata belongs to, without looking the path up again. Devices
+    /// count as plain files
+    pub fn from_metadata(metadata: &Metadata) -> PathType {
+        let mode = metadata.mode();
+        match mode & S_IFMT {
+            S_IFDIR => PathType::Directory,
+            S_IFLNK => PathType::Symlink,
+            S_IFIFO => PathType::FIFO,
+            S_IFSOCK => PathType::Socket,
+            S_IFREG if mode & (S_IXUSR | S_IXGRP | S_IXOTH) != 0 => PathType::Executable,
+            _ => PathType::File,
+        }
+    }
+}
+
+/// How much a file or a whole directory tree takes up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// The length of the files in bytes
+    pub apparent: u64,
+    /// The space allocated on the device in 512 byte blocks, like st_blocks
+    pub blocks: u64,
+}
+
+impl Usage {
+    pub fn from_metadata(metadata: &Metadata) -> Usage {
+        Usage {
+            apparent: metadata.size(),
+            blocks: metadata.blocks(),
+        }
+    }
+
+    fn add(&mut self, other: Usage) {
+        self.apparent += other.apparent;
+        self.blocks += other.blocks;
+    }
+}
+
+/// Walks directory trees to add up their size the way du does. Files with several hard links,
+/// and directories reached twice through links, are only counted the first time they are seen
+#[derive(Debug, Default)]
+pub struct DiskUsage {
+    /// Follow symbolic links instead of counting the links themselves
+    dereference: bool,
+    /// Device and inode of everything which could be reached a second time
+    seen: HashSet<(u64, u64)>,
+}
+
+impl DiskUsage {
+    pub fn new(dereference: bool) -> DiskUsage {
+        DiskUsage {
+            dereference,
+            seen: HashSet::new(),
+        }
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        if self.dereference {
+            path.metadata()
+        } else {
+            path.symlink_metadata()
+        }
+    }
+
+    /// Whether the file was counted already, which is then skipped
+    fn counted(&mut self, metadata: &Metadata) -> bool {
+        (metadata.is_dir() || metadata.nlink() > 1)
+            && !self.seen.insert((metadata.dev(), metadata.ino()))
+    }
+
+    /// The usage of the path and everything below it, None if it was counted already. `visit` is
+    /// called for every entry below the path once its usage is known, so directories come after
+    /// their contents. Entries which can't be read are passed to `error` along with what failed
+    pub fn walk(
+        &mut self,
+        path: &Path,
+        visit: &mut dyn FnMut(&Path, &Metadata, Usage),
+        error: &mut dyn FnMut(&str, &Path, &Error),
+    ) -> Result<Option<Usage>> {
+        let metadata = self.metadata(path)?;
+        if self.counted(&metadata) {
+            return Ok(None);
+        }
+        Ok(Some(self.walk_below(path, &metadata, visit, error)))
+    }
+
+    fn walk_below(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        visit: &mut dyn FnMut(&Path, &Metadata, Usage),
+        error: &mut dyn FnMut(&str, &Path, &Error),
+    ) -> Usage {
+        let mut usage = Usage::from_metadata(metadata);
+        if !metadata.is_dir() {
+            return usage;
+        }
+        let entries = match read_dir(path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                error("cannot read directory", path, &e);
+                return usage;
+            }
         };
-        group
-    }
-    fn owner(&self) -> String {
-        let owner: String;
-        unsafe {
-            let user_entry_pointer = getpwuid(self.uid());
-            // Check so pointer is not null
-            if user_entry_pointer.is_null() {
-                owner = String::from("unknown");
-                return owner;
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => {
+                    error("cannot read directory", path, &e);
+                    continue;
+                }
             };
-            let user_entry = user_entry_pointer.read();
-            dbg!(&user_entry);
-            let owner_raw = CString::from_raw(user_entry.pw_name);
-            owner = owner_raw.to_str().unwrap_or("unknown").to_string();
-        };
-        owner
+            let metadata = match self.metadata(&entry) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    error("cannot access", &entry, &e);
+                    continue;
+                }
+            };
+            if self.counted(&metadata) {
+                continue;
+            }
+            let entry_usage = self.walk_below(&entry, &metadata, visit, error);
+            visit(&entry, &metadata, entry_usage);
+            usage.add(entry_usage);
+        }
+        usage
     }
-}*/
+}
+
+pub trait MetadataPlus {
+    /// Name of the owning user, None if the uid has no name
+    fn owner(&self) -> Option<String>;
+    /// Name of the owning group, None if the gid has no name
+    fn group(&self) -> Option<String>;
+}
+
+thread_local! {
+    // Listing a large directory would otherwise hit the passwd and group databases once per
+    // entry, while most entries share the same few owners
+    static ID_NAMES: UsersCache = UsersCache::new();
+}
+
+impl MetadataPlus for Metadata {
+    fn owner(&self) -> Option<String> {
+        ID_NAMES.with(|names| {
+            names
+                .get_user_by_uid(self.uid())
+                .map(|user| user.name().to_string_lossy().to_string())
+        })
+    }
+    fn group(&self) -> Option<String> {
+        ID_NAMES.with(|names| {
+            names
+                .get_group_by_gid(self.gid())
+                .map(|group| group.name().to_string_lossy().to_string())
+        })
+    }
+}
 
 pub trait PermissionsPlus {
     fn mode_struct(&self) -> ModeWrapper;
@@ -219,19 +596,33 @@ impl PermissionsPlus for Permissions {
             read: (mode_bits & S_IRUSR) != 0,
             write: (mode_bits & S_IWUSR) != 0,
             execute: (mode_bits & S_IXUSR) != 0,
+            special: (mode_bits & S_ISUID) != 0,
         };
         let group = Mode {
             read: (mode_bits & S_IRGRP) != 0,
             write: (mode_bits & S_IWGRP) != 0,
             execute: (mode_bits & S_IXGRP) != 0,
+            special: (mode_bits & S_ISGID) != 0,
         };
         let others = Mode {
             read: (mode_bits & S_IROTH) != 0,
             write: (mode_bits & S_IWOTH) != 0,
             execute: (mode_bits & S_IXOTH) != 0,
+            special: (mode_bits & S_ISVTX) != 0,
+        };
+        // Same letters as GNU's filemodestring
+        let file_type = match mode_bits & S_IFMT {
+            S_IFREG => '-',
+            S_IFDIR => 'd',
+            S_IFLNK => 'l',
+            S_IFCHR => 'c',
+            S_IFBLK => 'b',
+            S_IFIFO => 'p',
+            S_IFSOCK => 's',
+            _ => '?',
         };
         ModeWrapper {
-            sticky_bit: (mode_bits & S_ISVTX) != 0,
+            file_type,
             owner,
             group,
             others,
@@ -240,32 +631,43 @@ impl PermissionsPlus for Permissions {
 }
 
 impl ModeWrapper {
+    pub fn file_type(&self) -> char {
+        self.file_type
+    }
+
     pub fn to_string(&self) -> String {
         format!(
             "{}{}{}{}",
-            if self.sticky_bit { "d" } else { "-" },
-            self.owner.to_string(),
-            self.group.to_string(),
-            self.others.to_string()
+            self.file_type,
+            self.owner.to_string('s'),
+            self.group.to_string('s'),
+            self.others.to_string('t')
         )
     }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct ModeWrapper {
-    sticky_bit: bool,
+    file_type: char,
     owner: Mode,
     group: Mode,
     others: Mode,
 }
 
 impl Mode {
-    pub fn to_string(&self) -> String {
+    /// The special bit replaces the execute letter with `special_char`, which is uppercase if the
+    /// execute bit isn't set
+    pub fn to_string(&self, special_char: char) -> String {
         format!(
             "{}{}{}",
-            if self.read { "r" } else { "-" },
-            if self.write { "w" } else { "-" },
-            if self.execute { "x" } else { "-" }
+            if self.read { 'r' } else { '-' },
+            if self.write { 'w' } else { '-' },
+            match (self.special, self.execute) {
+                (true, true) => special_char,
+                (true, false) => special_char.to_ascii_uppercase(),
+                (false, true) => 'x',
+                (false, false) => '-',
+            }
         )
     }
 }
@@ -275,6 +677,8 @@ pub struct Mode {
     read: bool,
     write: bool,
     execute: bool,
+    /// Setuid for the owner, setgid for the group and the sticky bit for others
+    special: bool,
 }
 
 pub fn wrap<T: Any, M: Display>(result: Result<T>, prog: M, silent: bool) -> T {
@@ -310,3 +714,147 @@ pub fn prompt<T: Display>(question: T, d: bool) -> bool {
         _ => return d,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filevercmp_matches_gnu_order() {
+        // In the order GNU ls 9.1 lists them with -v in the C locale
+        let names: [&[u8]; 39] = [
+            b"",
+            b".",
+            b"..",
+            b".A",
+            b".a~",
+            b".a",
+            b".zz~",
+            b".zz",
+            b".zz.~1~",
+            b".0",
+            b".9",
+            b".zz.0",
+            b".\x01",
+            b"0",
+            b"9",
+            b"A",
+            b"Z",
+            b"a~",
+            b"a",
+            b"a.b~",
+            b"a.b",
+            b"a.bc",
+            b"a02",
+            b"a2",
+            b"a10",
+            b"a+",
+            b"a.",
+            b"a..a",
+            b"a.+",
+            b"file-1.2.tar.gz",
+            b"file-1.2a.tar.gz",
+            b"file-1.10.tar.gz",
+            b"x.tar.gz",
+            b"x.1.tar.gz",
+            b"zz.~1~",
+            b"zz.0",
+            b"zz.0.txt",
+            b"\x01",
+            b"#.b#",
+        ];
+        for (i, a) in names.iter().enumerate() {
+            for b in &names[i + 1..] {
+                // ls breaks ties by comparing the bytes
+                let ordering = filevercmp(a, b).then(a.cmp(b));
+                assert_eq!(ordering, Ordering::Less, "{:?} {:?}", a, b);
+                assert_eq!(filevercmp(b, a).then(b.cmp(a)), Ordering::Greater);
+            }
+        }
+    }
+
+    #[test]
+    fn filevercmp_numbers_and_suffixes() {
+        assert_eq!(filevercmp(b"a2", b"a10"), Ordering::Less);
+        assert_eq!(filevercmp(b"a02", b"a2"), Ordering::Equal);
+        // Backups with '~' come before the file itself
+        assert_eq!(filevercmp(b"a~", b"a"), Ordering::Less);
+        // Suffixes only count when the rest is the same
+        assert_eq!(filevercmp(b"a.tar.gz", b"a.zip"), Ordering::Less);
+        assert_eq!(filevercmp(b"a1.zip", b"a2.tar.gz"), Ordering::Less);
+        assert_eq!(filevercmp(b"x.tar.gz", b"x.1.tar.gz"), Ordering::Less);
+        assert_eq!(filevercmp(b"same", b"same"), Ordering::Equal);
+    }
+
+    /// Formats each byte count with the block size, the sizes are what GNU ls 9.1 prints with -l
+    fn check_sizes(block_size: BlockSize, sizes: &[(u64, &str)]) {
+        for (bytes, expected) in sizes {
+            assert_eq!(block_size.format(*bytes, 1), *expected, "{} bytes", bytes);
+        }
+    }
+
+    #[test]
+    fn human_readable_rounds_up() {
+        check_sizes(
+            BlockSize::human(true),
+            &[
+                (0, "0"),
+                (999, "999"),
+                (1023, "1023"),
+                (1024, "1.0K"),
+                (1025, "1.1K"),
+                (1536, "1.5K"),
+                (9999, "9.8K"),
+                (10239, "10K"),
+                (10240, "10K"),
+                (10241, "11K"),
+                (1047552, "1023K"),
+                (1048063, "1.0M"),
+                (1048575, "1.0M"),
+                (1048576, "1.0M"),
+                (1073741823, "1.0G"),
+            ],
+        );
+    }
+
+    #[test]
+    fn si_rounds_up() {
+        check_sizes(
+            BlockSize::human(false),
+            &[
+                (999, "999"),
+                (1000, "1.0k"),
+                (1023, "1.1k"),
+                (9999, "10k"),
+                (10000, "10k"),
+                (10239, "11k"),
+                (999499, "1.0M"),
+                (999500, "1.0M"),
+                (1000000, "1.0M"),
+                (1048576, "1.1M"),
+            ],
+        );
+    }
+
+    #[test]
+    fn fixed_block_sizes() {
+        check_sizes(
+            "1K".parse().unwrap(),
+            &[
+                (0, "0"),
+                (1, "1"),
+                (1024, "1"),
+                (1025, "2"),
+                (999500, "977"),
+            ],
+        );
+        check_sizes("K".parse().unwrap(), &[(1023, "1K"), (1048576, "1024K")]);
+        check_sizes(
+            "KB".parse().unwrap(),
+            &[(0, "0kB"), (999, "1kB"), (1023, "2kB"), (999500, "1000kB")],
+        );
+        // Blocks from -s are 512 bytes each
+        assert_eq!(BlockSize::new(1024).format(3, 512), "2");
+        assert_eq!(BlockSize::human(true).format(3, 512), "1.5K");
+    }
+}